@@ -0,0 +1,132 @@
+//! Start at login: a per-user entry that launches the app with
+//! [`MINIMIZED_FLAG`], so a session starts with only the tray icon.
+//!
+//! - Linux and BSDs: an XDG autostart file, `~/.config/autostart/rustine.desktop`
+//! - macOS: a launch agent, `~/Library/LaunchAgents/io.github.medyll.rustine.plist`
+//! - Windows: a script in the Startup folder, `%APPDATA%\Microsoft\Windows\Start Menu\Programs\Startup\Rustine.cmd`
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Command-line flag: start in the tray, without opening the main window.
+pub const MINIMIZED_FLAG: &str = "--minimized";
+
+/// Create or remove the login entry so it matches `enabled`
+/// (`WindowConfig::start_on_login`). The entry points at the running executable.
+pub fn sync(enabled: bool) -> Result<()> {
+    let os = std::env::consts::OS;
+    let path =
+        entry_path(os).ok_or_else(|| anyhow!("start at login is not supported on {}", os))?;
+    if !enabled {
+        return match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(anyhow!("failed to remove {}: {}", path.display(), e)),
+        };
+    }
+    let exe = std::env::current_exe()?;
+    let content = entry_content(os, &exe).ok_or_else(|| anyhow!("unsupported OS {}", os))?;
+    if fs::read_to_string(&path).is_ok_and(|current| current == content) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, content).map_err(|e| anyhow!("failed to write {}: {}", path.display(), e))
+}
+
+/// Where the login entry lives for `os` (as in `std::env::consts::OS`).
+fn entry_path(os: &str) -> Option<PathBuf> {
+    match os {
+        "linux" | "freebsd" | "dragonfly" | "netbsd" | "openbsd" => Some(
+            dirs::config_dir()?
+                .join("autostart")
+                .join("rustine.desktop"),
+        ),
+        "macos" => Some(
+            dirs::home_dir()?
+                .join("Library/LaunchAgents")
+                .join("io.github.medyll.rustine.plist"),
+        ),
+        "windows" => Some(
+            dirs::config_dir()?
+                .join(r"Microsoft\Windows\Start Menu\Programs\Startup")
+                .join("Rustine.cmd"),
+        ),
+        _ => None,
+    }
+}
+
+/// The login entry for `os` that runs `exe` minimized.
+fn entry_content(os: &str, exe: &Path) -> Option<String> {
+    let exe = exe.to_string_lossy();
+    match os {
+        "linux" | "freebsd" | "dragonfly" | "netbsd" | "openbsd" => {
+            // Exec quoting rules of the desktop entry spec
+            let quoted: String = exe
+                .chars()
+                .flat_map(|c| match c {
+                    '"' | '`' | '$' | '\\' => vec!['\\', c],
+                    _ => vec![c],
+                })
+                .collect();
+            Some(format!(
+                "[Desktop Entry]\nType=Application\nName=Rustine\nExec=\"{}\" {}\nX-GNOME-Autostart-enabled=true\n",
+                quoted, MINIMIZED_FLAG
+            ))
+        }
+        "macos" => {
+            let escaped = exe
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            Some(format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>io.github.medyll.rustine</string>
+  <key>ProgramArguments</key>
+  <array>
+    <string>{}</string>
+    <string>{}</string>
+  </array>
+  <key>RunAtLoad</key>
+  <true/>
+</dict>
+</plist>
+"#,
+                escaped, MINIMIZED_FLAG
+            ))
+        }
+        "windows" => Some(format!("@start \"\" \"{}\" {}\r\n", exe, MINIMIZED_FLAG)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_launch_the_exe_minimized() {
+        let desktop = entry_content("linux", Path::new("/opt/my $apps/rustine")).unwrap();
+        assert!(desktop.starts_with("[Desktop Entry]\n"));
+        assert!(desktop.contains("Exec=\"/opt/my \\$apps/rustine\" --minimized\n"));
+
+        let plist = entry_content("macos", Path::new("/Applications/R&D.app/rustine")).unwrap();
+        assert!(plist.contains("<string>/Applications/R&amp;D.app/rustine</string>"));
+        assert!(plist.contains("<string>--minimized</string>"));
+
+        let script = entry_content("windows", Path::new(r"C:\Apps\rustine.exe")).unwrap();
+        assert_eq!(
+            script,
+            "@start \"\" \"C:\\Apps\\rustine.exe\" --minimized\r\n"
+        );
+
+        assert_eq!(entry_content("ios", Path::new("/rustine")), None);
+        assert_eq!(entry_path("ios"), None);
+    }
+}
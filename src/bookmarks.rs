@@ -0,0 +1,383 @@
+//! Netscape bookmark file (`bookmarks.html`) import.
+//!
+//! Every major browser exports bookmarks in this format: nested `<DL>` lists
+//! where `<DT><H3>` opens a folder and `<DT><A HREF=...>` is a bookmark.
+
+use crate::db::DbHandle;
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use scraper::{ElementRef, Html};
+use std::collections::HashSet;
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    /// `ADD_DATE`, in unix seconds.
+    pub add_date: Option<i64>,
+    /// Enclosing folders, outermost first.
+    pub folders: Vec<String>,
+    pub icon: Option<BookmarkIcon>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookmarkIcon {
+    /// `ICON_URI` when the browser exported it, else the data URI's media type.
+    pub src_url: String,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+/// How bookmark folders become tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FolderTags {
+    /// Ignore folders.
+    None,
+    /// Tag with the folder directly containing the bookmark.
+    Innermost,
+    /// Tag with every enclosing folder.
+    All,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    /// Already saved, duplicated in the file, or not an http(s) link.
+    pub skipped: usize,
+    pub failed: usize,
+    /// One message per failed bookmark.
+    pub errors: Vec<String>,
+}
+
+/// Parse a Netscape bookmark file into a flat list, in document order.
+pub fn parse_netscape(html: &str) -> Vec<Bookmark> {
+    let doc = Html::parse_document(html);
+    let mut out = Vec::new();
+    walk(doc.root_element(), &mut Vec::new(), &mut out);
+    out
+}
+
+fn walk(parent: ElementRef, folders: &mut Vec<String>, out: &mut Vec<Bookmark>) {
+    // Some exporters put a folder's <DL> next to its <DT> instead of inside it.
+    let mut pending_folder: Option<Option<String>> = None;
+    for child in parent.children().filter_map(ElementRef::wrap) {
+        match child.value().name() {
+            "dt" => {
+                pending_folder = None;
+                let mut folder = None;
+                let mut has_list = false;
+                for part in child.children().filter_map(ElementRef::wrap) {
+                    match part.value().name() {
+                        "h3" => folder = Some(folder_name(part)),
+                        "a" => {
+                            if let Some(b) = bookmark(part, folders) {
+                                out.push(b);
+                            }
+                        }
+                        "dl" => {
+                            has_list = true;
+                            with_folder(folder.clone().flatten(), folders, |f| walk(part, f, out));
+                        }
+                        _ => walk(part, folders, out),
+                    }
+                }
+                if !has_list {
+                    pending_folder = folder;
+                }
+            }
+            "dl" => {
+                let folder = pending_folder.take().flatten();
+                with_folder(folder, folders, |f| walk(child, f, out));
+            }
+            _ => walk(child, folders, out),
+        }
+    }
+}
+
+/// `Some(name)` for a user folder, `None` for the browser's root folders
+/// (toolbar, unfiled) which should not become tags.
+fn folder_name(h3: ElementRef) -> Option<String> {
+    let attrs = h3.value();
+    if attrs.attr("personal_toolbar_folder").is_some()
+        || attrs.attr("unfiled_bookmarks_folder").is_some()
+    {
+        return None;
+    }
+    let name = h3.text().collect::<String>().trim().to_string();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn with_folder(
+    folder: Option<String>,
+    folders: &mut Vec<String>,
+    f: impl FnOnce(&mut Vec<String>),
+) {
+    match folder {
+        Some(name) => {
+            folders.push(name);
+            f(folders);
+            folders.pop();
+        }
+        None => f(folders),
+    }
+}
+
+fn bookmark(a: ElementRef, folders: &[String]) -> Option<Bookmark> {
+    let attrs = a.value();
+    let url = attrs.attr("href")?.trim().to_string();
+    if url.is_empty() {
+        return None;
+    }
+    let title = a.text().collect::<String>().trim().to_string();
+    let add_date = attrs
+        .attr("add_date")
+        .and_then(|d| d.trim().parse::<i64>().ok())
+        // some browsers export microseconds
+        .map(|d| {
+            if d > 100_000_000_000 {
+                d / 1_000_000
+            } else {
+                d
+            }
+        });
+    let icon = attrs.attr("icon").and_then(|uri| {
+        let (mime, data) = decode_data_uri(uri)?;
+        let src_url = attrs
+            .attr("icon_uri")
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("data:{}", mime));
+        Some(BookmarkIcon {
+            src_url,
+            mime,
+            data,
+        })
+    });
+    Some(Bookmark {
+        title,
+        url,
+        add_date,
+        folders: folders.to_vec(),
+        icon,
+    })
+}
+
+/// Decode a base64 `data:` URI into its media type and bytes.
+fn decode_data_uri(uri: &str) -> Option<(String, Vec<u8>)> {
+    let rest = uri.trim().strip_prefix("data:")?;
+    let (meta, payload) = rest.split_once(',')?;
+    let mime = meta.strip_suffix(";base64")?;
+    let data = STANDARD.decode(payload.trim()).ok()?;
+    if data.is_empty() {
+        return None;
+    }
+    let mime = if mime.is_empty() { "image/png" } else { mime };
+    Some((mime.to_string(), data))
+}
+
+/// Key used to detect bookmarks that are already saved.
+fn dedup_key(url: &str) -> String {
+    crate::urlnorm::canonical(url).unwrap_or_else(|| url.to_string())
+}
+
+/// Import a Netscape bookmark file into the library. Bookmarks whose URL is
+/// already saved (or repeated in the file) are skipped; embedded icons are
+/// stored for sites that have no metadata yet.
+pub fn import_netscape(
+    db: &DbHandle,
+    html: &str,
+    folder_tags: FolderTags,
+) -> Result<ImportSummary> {
+    let mut seen: HashSet<String> = db
+        .list_recent(i64::MAX)?
+        .iter()
+        .map(|r| dedup_key(&r.url))
+        .collect();
+    let mut summary = ImportSummary::default();
+
+    for b in parse_netscape(html) {
+        let parsed = match Url::parse(&b.url) {
+            Ok(u) => u,
+            Err(e) => {
+                summary.failed += 1;
+                summary.errors.push(format!("{}: {}", b.url, e));
+                continue;
+            }
+        };
+        if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+            summary.skipped += 1;
+            continue;
+        }
+        let url = parsed.to_string();
+        if !seen.insert(dedup_key(&url)) {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let label = if b.title.is_empty() {
+            parsed.host_str().unwrap_or(&url).to_string()
+        } else {
+            b.title.clone()
+        };
+        let id = match db.insert_url(
+            &label,
+            &url,
+            b.add_date.unwrap_or_else(|| Utc::now().timestamp()),
+        ) {
+            Ok(id) => id,
+            Err(e) => {
+                summary.failed += 1;
+                summary.errors.push(format!("{}: {}", url, e));
+                continue;
+            }
+        };
+        summary.added += 1;
+
+        let tags: &[String] = match folder_tags {
+            FolderTags::None => &[],
+            FolderTags::Innermost => b.folders.last().map(std::slice::from_ref).unwrap_or(&[]),
+            FolderTags::All => &b.folders,
+        };
+        for tag in tags {
+            if let Err(e) = db.tag_url(id, tag) {
+                eprintln!("bookmark import: failed to tag {} with {}: {}", url, tag, e);
+            }
+        }
+
+        if let (Some(icon), Some(origin)) = (&b.icon, crate::urlnorm::origin(&url)) {
+            if let Err(e) = store_icon(db, &origin, icon) {
+                eprintln!("bookmark import: failed to store icon for {}: {}", url, e);
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn store_icon(db: &DbHandle, origin: &str, icon: &BookmarkIcon) -> Result<()> {
+    // Don't clobber metadata (or icons) fetched from the live site.
+    if db.get_site_meta_by_origin(origin)?.is_some() {
+        return Ok(());
+    }
+    db.upsert_site_meta(origin, None, None, None, None)?;
+    if let Some(site) = db.get_site_meta_by_origin(origin)? {
+        db.insert_icon(
+            site.id,
+            &icon.src_url,
+            None,
+            None,
+            Some(&icon.mime),
+            icon.data.clone(),
+            Some(Utc::now().timestamp()),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const FIXTURE: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file. -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1600000000" PERSONAL_TOOLBAR_FOLDER="true">Bookmarks bar</H3>
+    <DL><p>
+        <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1600000001" ICON="data:image/png;base64,iVBORw0KGgo=">Rust</A>
+        <DT><H3 ADD_DATE="1600000002">Work</H3>
+        <DL><p>
+            <DT><A HREF="https://github.com/medyll/rustine" ADD_DATE="1600000003">Rustine repo</A>
+            <DT><H3>Docs</H3>
+            <DL><p>
+                <DT><A HREF="https://docs.rs/" ADD_DATE="1600000004000000">Docs.rs</A>
+            </DL><p>
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="javascript:alert(1)">Bookmarklet</A>
+    <DT><A HREF="https://www.rust-lang.org">Rust again</A>
+    <DT><A HREF="http://[broken">Broken</A>
+    <DT><A HREF="https://example.com/untitled"></A>
+</DL><p>
+"#;
+
+    #[test]
+    fn test_parse_netscape_folders_dates_and_icons() {
+        let marks = parse_netscape(FIXTURE);
+        assert_eq!(marks.len(), 7);
+
+        assert_eq!(marks[0].title, "Rust");
+        assert!(marks[0].folders.is_empty(), "toolbar folder is not a tag");
+        assert_eq!(marks[0].add_date, Some(1600000001));
+        let icon = marks[0].icon.as_ref().expect("embedded icon");
+        assert_eq!(icon.mime, "image/png");
+        assert_eq!(&icon.data[..4], b"\x89PNG");
+
+        assert_eq!(marks[1].title, "Rustine repo");
+        assert_eq!(marks[1].folders, vec!["Work".to_string()]);
+        assert_eq!(
+            marks[2].folders,
+            vec!["Work".to_string(), "Docs".to_string()]
+        );
+        assert_eq!(
+            marks[2].add_date,
+            Some(1600000004),
+            "microseconds are scaled"
+        );
+
+        assert!(marks[3].folders.is_empty());
+    }
+
+    #[test]
+    fn test_parse_sibling_folder_list() {
+        let html =
+            r#"<DL><DT><H3>Outer</H3></DT><DL><DT><A HREF="https://a.example/">A</A></DL></DL>"#;
+        let marks = parse_netscape(html);
+        assert_eq!(marks.len(), 1);
+        assert_eq!(marks[0].folders, vec!["Outer".to_string()]);
+    }
+
+    #[test]
+    fn test_import_summary_tags_and_dedup() -> Result<()> {
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        db.insert_url("Existing", "https://docs.rs", 1)?;
+
+        let summary = import_netscape(&db, FIXTURE, FolderTags::All)?;
+        assert_eq!(summary.added, 3, "{:?}", summary);
+        // docs.rs already saved, bookmarklet, duplicate rust-lang.org
+        assert_eq!(summary.skipped, 3, "{:?}", summary);
+        assert_eq!(summary.failed, 1, "{:?}", summary);
+
+        let repo = db
+            .list_recent(10)?
+            .into_iter()
+            .find(|r| r.label == "Rustine repo")
+            .unwrap();
+        assert_eq!(repo._timestamp, 1600000003);
+        assert_eq!(repo.tags, vec!["Work".to_string()]);
+        assert!(db
+            .list_recent(10)?
+            .iter()
+            .any(|r| r.label == "example.com" && r.url == "https://example.com/untitled"));
+
+        let rust = db
+            .list_recent(10)?
+            .into_iter()
+            .find(|r| r.label == "Rust")
+            .unwrap();
+        assert_eq!(rust.icon_mime.as_deref(), Some("image/png"));
+
+        // importing again adds nothing
+        let again = import_netscape(&db, FIXTURE, FolderTags::All)?;
+        assert_eq!(again.added, 0);
+        Ok(())
+    }
+}
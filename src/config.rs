@@ -0,0 +1,288 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Environment variable overriding the config file location.
+pub const CONFIG_PATH_ENV: &str = "RUSTINE_CONFIG_PATH";
+
+/// Application settings, stored as JSON. Missing fields take their defaults so
+/// older files keep loading as settings are added.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub http: HttpConfig,
+    pub metadata: MetadataConfig,
+    pub network: NetworkConfig,
+    pub links: LinkCheckConfig,
+    pub window: WindowConfig,
+    pub hotkeys: HotkeyConfig,
+}
+
+/// Settings for metadata and icon fetches (see `crate::http`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub connect_timeout_secs: u64,
+    /// Whole request, including reading the body.
+    pub timeout_secs: u64,
+    pub user_agent: String,
+    /// Responses larger than this are rejected.
+    pub max_body_bytes: u64,
+    pub max_redirects: usize,
+    /// Proxy URL used for every scheme (e.g. `http://proxy:3128`).
+    /// When unset the system proxy variables apply.
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            connect_timeout_secs: 5,
+            timeout_secs: 20,
+            user_agent: "rustine/0.1 (+https://github.com/medyll/rustine)".to_string(),
+            max_body_bytes: 5 * 1024 * 1024,
+            max_redirects: 5,
+            proxy: None,
+        }
+    }
+}
+
+/// Background metadata refresh (see `crate::refresh`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataConfig {
+    /// Site metadata older than this is fetched again.
+    pub refresh_after_days: u64,
+    /// How often the worker looks for stale sites.
+    pub check_interval_secs: u64,
+}
+
+impl Default for MetadataConfig {
+    fn default() -> Self {
+        MetadataConfig {
+            refresh_after_days: 7,
+            check_interval_secs: 15 * 60,
+        }
+    }
+}
+
+/// Scheduled dead-link checks (see `crate::linkcheck`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkCheckConfig {
+    /// Saved URLs are checked again after this many days; 0 turns scheduled
+    /// checks off (on-demand checks still work).
+    pub recheck_after_days: u64,
+}
+
+impl Default for LinkCheckConfig {
+    fn default() -> Self {
+        LinkCheckConfig {
+            recheck_after_days: 7,
+        }
+    }
+}
+
+/// Main window and tray residency (see `crate::ui::launch`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Closing the main window leaves the app running in the tray; otherwise
+    /// it quits.
+    pub close_to_tray: bool,
+    /// Start with the session, in the tray only (see `crate::autostart`).
+    pub start_on_login: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            close_to_tray: true,
+            start_on_login: false,
+        }
+    }
+}
+
+/// Global keyboard shortcuts (see `crate::hotkeys`), written like
+/// `CmdOrCtrl+Alt+R`; an empty string turns one off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    /// Open the launcher: type to find a saved URL and open it.
+    pub launcher: String,
+    /// Save the URL on the clipboard.
+    pub add_clipboard: String,
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        HotkeyConfig {
+            launcher: "CmdOrCtrl+Alt+R".to_string(),
+            add_clipboard: "CmdOrCtrl+Alt+A".to_string(),
+        }
+    }
+}
+
+/// When metadata and icons may be fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchPolicy {
+    /// When a URL is added or opened, on manual refresh and in the background.
+    #[default]
+    Always,
+    /// Only when a URL is added or refreshed by hand.
+    OnAdd,
+    /// Never: the app makes no metadata requests at all.
+    Never,
+}
+
+/// Network policy for metadata fetches (see `crate::metadata`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub fetch_policy: FetchPolicy,
+    /// Hosts never contacted for metadata; an entry also covers its subdomains.
+    pub blocked_hosts: Vec<String>,
+}
+
+impl NetworkConfig {
+    pub fn is_host_blocked(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.blocked_hosts.iter().any(|entry| {
+            let entry = entry
+                .trim()
+                .trim_start_matches("*.")
+                .trim_end_matches('.')
+                .to_ascii_lowercase();
+            !entry.is_empty() && (host == entry || host.ends_with(&format!(".{}", entry)))
+        })
+    }
+}
+
+impl AppConfig {
+    /// Read the config at `path`; a missing file yields the defaults.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| anyhow!("invalid config {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AppConfig::default()),
+            Err(e) => Err(anyhow!("failed to read config {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Resolve where the config lives: `RUSTINE_CONFIG_PATH` when set, otherwise
+/// `config.json` next to the default database location.
+pub fn resolve_config_path() -> Result<PathBuf> {
+    config_path_from(std::env::var_os(CONFIG_PATH_ENV), dirs::data_dir())
+}
+
+fn config_path_from(env_override: Option<OsString>, data_dir: Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(p) = env_override.filter(|p| !p.is_empty()) {
+        return Ok(PathBuf::from(p));
+    }
+    let base = data_dir.ok_or_else(|| {
+        anyhow!(
+            "could not determine the user data directory; set {} instead",
+            CONFIG_PATH_ENV
+        )
+    })?;
+    Ok(crate::db::app_dir(base).join("config.json"))
+}
+
+static GLOBAL_CONFIG: OnceCell<RwLock<AppConfig>> = OnceCell::new();
+
+pub fn set_global(config: AppConfig) -> Result<()> {
+    GLOBAL_CONFIG
+        .set(RwLock::new(config))
+        .map_err(|_| anyhow!("global config already set"))
+}
+
+/// The active configuration (defaults until `set_global` is called).
+pub fn current() -> AppConfig {
+    GLOBAL_CONFIG
+        .get()
+        .and_then(|c| c.read().ok().map(|c| c.clone()))
+        .unwrap_or_default()
+}
+
+/// Change the active configuration and save it to `resolve_config_path()`.
+pub fn update(change: impl FnOnce(&mut AppConfig)) -> Result<()> {
+    let lock = GLOBAL_CONFIG
+        .get()
+        .ok_or_else(|| anyhow!("global config not set"))?;
+    let mut config = lock
+        .write()
+        .map_err(|_| anyhow!("global config lock poisoned"))?;
+    change(&mut config);
+    config.save(&resolve_config_path()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_missing_file_and_partial_fields_use_defaults() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("rustine-config-{}", std::process::id()));
+        let path = dir.join("config.json");
+        assert_eq!(AppConfig::load(&path)?, AppConfig::default());
+
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            &path,
+            r#"{"http": {"proxy": "http://proxy:3128", "timeout_secs": 3}}"#,
+        )?;
+        let cfg = AppConfig::load(&path)?;
+        assert_eq!(cfg.http.proxy.as_deref(), Some("http://proxy:3128"));
+        assert_eq!(cfg.http.timeout_secs, 3);
+        assert_eq!(cfg.http.connect_timeout_secs, 5);
+        assert!(cfg.window.close_to_tray);
+        assert_eq!(cfg.hotkeys, HotkeyConfig::default());
+
+        cfg.save(&path)?;
+        assert_eq!(AppConfig::load(&path)?, cfg);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_network_policy_parses_and_blocks_subdomains() -> Result<()> {
+        let cfg: AppConfig = serde_json::from_str(
+            r#"{"network": {"fetch_policy": "on_add", "blocked_hosts": ["corp.example", "*.intra"]}}"#,
+        )?;
+        assert_eq!(cfg.network.fetch_policy, FetchPolicy::OnAdd);
+        assert!(cfg.network.is_host_blocked("corp.example"));
+        assert!(cfg.network.is_host_blocked("Wiki.Corp.Example."));
+        assert!(cfg.network.is_host_blocked("jira.intra"));
+        assert!(!cfg.network.is_host_blocked("notcorp.example"));
+        assert!(!cfg.network.is_host_blocked("example.com"));
+        assert_eq!(
+            AppConfig::default().network.fetch_policy,
+            FetchPolicy::Always
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_path_env_override_wins() -> Result<()> {
+        let p = config_path_from(Some("/tmp/x.json".into()), Some(PathBuf::from("/data")))?;
+        assert_eq!(p, PathBuf::from("/tmp/x.json"));
+        let p = config_path_from(None, Some(PathBuf::from("/data")))?;
+        assert!(p.starts_with("/data"));
+        assert!(p.ends_with("config.json"));
+        Ok(())
+    }
+}
@@ -2,8 +2,23 @@ use anyhow::{anyhow, Result};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use once_cell::sync::OnceCell;
 use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::thread;
 
+mod dedup;
+mod events;
+mod export;
+mod links;
+mod migrations;
+
+pub use dedup::DuplicateGroup;
+pub use events::{DbEvent, Subscription};
+pub use export::ExportFormat;
+pub use links::{DueLink, LinkCheck};
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct SiteMeta {
@@ -13,10 +28,74 @@ pub struct SiteMeta {
     pub description: Option<String>,
     pub manifest_url: Option<String>,
     pub metadata_fetched_at: Option<i64>,
+    pub page: PageDetails,
+    /// Preview image (`og:image`/`twitter:image`), when one was downloaded.
+    pub preview_mime: Option<String>,
+    pub preview_data: Option<Vec<u8>>,
+    /// Validators of the last successful page fetch, for conditional requests.
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Consecutive failed fetches; no new attempt before `next_fetch_at`.
+    pub fetch_failures: i64,
+    pub next_fetch_at: Option<i64>,
+    pub last_fetch_error: Option<String>,
+}
+
+/// An origin due for a metadata refresh and the URL to fetch for it (its
+/// oldest saved URL).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaleSite {
+    pub origin: String,
+    pub url: String,
+}
+
+/// Delay before retrying an origin after `failures` consecutive failed
+/// fetches: one hour, doubling each time, capped at a week.
+pub fn fetch_backoff_secs(failures: i64) -> i64 {
+    const BASE: i64 = 3600;
+    const CAP: i64 = 7 * 86400;
+    let exp = failures.saturating_sub(1).clamp(0, 20) as u32;
+    BASE.saturating_mul(1 << exp).min(CAP)
+}
+
+/// What a site's page declares about itself: `<title>`, OpenGraph and Twitter
+/// card tags, `application-name` and `theme-color`. Image fields are URLs.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct PageDetails {
+    pub title: Option<String>,
+    pub og_site_name: Option<String>,
+    pub og_title: Option<String>,
+    pub og_description: Option<String>,
+    pub og_image: Option<String>,
+    pub twitter_title: Option<String>,
+    pub twitter_description: Option<String>,
+    pub twitter_image: Option<String>,
+    pub application_name: Option<String>,
+    pub theme_color: Option<String>,
+}
+
+/// `site_meta` columns backing `PageDetails`, in field order.
+const PAGE_DETAILS_COLUMNS: &str = "title, og_site_name, og_title, og_description, og_image,
+    twitter_title, twitter_description, twitter_image, application_name, theme_color";
+
+/// Read `PageDetails` from `PAGE_DETAILS_COLUMNS` starting at column `first`.
+fn page_details_from_row(row: &rusqlite::Row, first: usize) -> rusqlite::Result<PageDetails> {
+    Ok(PageDetails {
+        title: row.get(first)?,
+        og_site_name: row.get(first + 1)?,
+        og_title: row.get(first + 2)?,
+        og_description: row.get(first + 3)?,
+        og_image: row.get(first + 4)?,
+        twitter_title: row.get(first + 5)?,
+        twitter_description: row.get(first + 6)?,
+        twitter_image: row.get(first + 7)?,
+        application_name: row.get(first + 8)?,
+        theme_color: row.get(first + 9)?,
+    })
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Icon {
     pub id: i64,
     pub site_id: i64,
@@ -37,6 +116,143 @@ pub struct UrlRecord {
     pub site_name: Option<String>,
     pub icon_mime: Option<String>,
     pub icon_data: Option<Vec<u8>>,
+    pub is_favorite: bool,
+    pub tags: Vec<String>,
+    /// The user opted this URL out of metadata fetching.
+    pub no_fetch: bool,
+    /// The latest link check found the URL broken.
+    pub link_broken: bool,
+    /// Where the latest successful check was redirected to, if elsewhere.
+    pub moved_to: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    /// Number of URLs carrying this tag.
+    pub url_count: i64,
+}
+
+/// Separator used to pack tag names into a single column.
+const TAG_SEPARATOR: char = '\u{1f}';
+
+/// A full-text search result: the matching row plus a snippet of the best
+/// matching field, with matches wrapped in `SNIPPET_OPEN`/`SNIPPET_CLOSE`.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub record: UrlRecord,
+    pub snippet: String,
+}
+
+/// Marker inserted before a highlighted match in `SearchHit::snippet`.
+pub const SNIPPET_OPEN: char = '\u{2}';
+/// Marker inserted after a highlighted match in `SearchHit::snippet`.
+pub const SNIPPET_CLOSE: char = '\u{3}';
+
+/// Split a snippet into `(text, highlighted)` segments for rendering.
+pub fn snippet_segments(snippet: &str) -> Vec<(String, bool)> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut highlighted = false;
+    for ch in snippet.chars() {
+        if ch == SNIPPET_OPEN || ch == SNIPPET_CLOSE {
+            if !current.is_empty() {
+                segments.push((std::mem::take(&mut current), highlighted));
+            }
+            highlighted = ch == SNIPPET_OPEN;
+        } else {
+            current.push(ch);
+        }
+    }
+    if !current.is_empty() {
+        segments.push((current, highlighted));
+    }
+    segments
+}
+
+/// Turn free-form user input into an FTS5 query: every whitespace-separated
+/// term becomes a quoted prefix match, all terms required. `None` if blank.
+fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|t| format!("\"{}\"*", t.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Columns expected by `url_record_from_row`, in order.
+const URL_RECORD_COLUMNS: &str = "u.id, u.label, u.url, u.timestamp, m.site_name, ic.mime, ic.data,
+    EXISTS(SELECT 1 FROM favorites fv WHERE fv.url_id = u.id),
+    (SELECT group_concat(t.name, char(31)) FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+        WHERE ut.url_id = u.id),
+    u.no_fetch, lc.status, lc.error IS NOT NULL, lc.final_url";
+
+/// `ORDER BY` terms ranking a site's icons for display at `$px` pixels: the
+/// smallest icon at least that large, else the largest smaller one; icons of
+/// unknown size come last; newest first among equals.
+macro_rules! best_icon_order {
+    ($px:literal) => {
+        concat!(
+            "CASE WHEN width IS NULL OR height IS NULL THEN 2 WHEN MIN(width, height) >= ",
+            $px,
+            " THEN 0 ELSE 1 END, CASE WHEN MIN(width, height) >= ",
+            $px,
+            " THEN MIN(width, height) ELSE -MIN(width, height) END, fetched_at DESC, id DESC"
+        )
+    };
+}
+pub(crate) use best_icon_order;
+
+/// Joins providing `m` (site metadata matched on the normalized origin),
+/// `lc` (latest link check) and `ic` (icon for a 16px list row: an SVG when the site has one, since the
+/// webview scales it cleanly, else the best raster) for `URL_RECORD_COLUMNS`.
+const URL_RECORD_JOINS: &str = concat!(
+    "LEFT JOIN site_meta m ON m.origin = u.origin
+    LEFT JOIN icons ic ON ic.id = (
+        SELECT id FROM icons WHERE site_id = m.id
+        ORDER BY CASE WHEN mime = 'image/svg+xml' THEN 0 ELSE 1 END, ",
+    best_icon_order!("16"),
+    " LIMIT 1
+    )
+    LEFT JOIN link_checks lc ON lc.id = (
+        SELECT id FROM link_checks WHERE url_id = u.id ORDER BY checked_at DESC, id DESC LIMIT 1
+    )"
+);
+
+fn url_record_from_row(row: &rusqlite::Row) -> rusqlite::Result<UrlRecord> {
+    let url: String = row.get(2)?;
+    let link_status: Option<u16> = row.get(10)?;
+    let link_broken = links::is_broken(link_status, row.get(11)?);
+    let moved_to = row
+        .get::<_, Option<String>>(12)?
+        .filter(|to| !link_broken && *to != url);
+    Ok(UrlRecord {
+        id: row.get(0)?,
+        label: row.get(1)?,
+        url,
+        _timestamp: row.get(3)?,
+        site_name: row.get(4)?,
+        icon_mime: row.get(5)?,
+        icon_data: row.get(6)?,
+        is_favorite: row.get(7)?,
+        tags: row
+            .get::<_, Option<String>>(8)?
+            .map(|packed| {
+                let mut tags: Vec<String> =
+                    packed.split(TAG_SEPARATOR).map(|t| t.to_string()).collect();
+                tags.sort_by_key(|t| t.to_lowercase());
+                tags
+            })
+            .unwrap_or_default(),
+        no_fetch: row.get(9)?,
+        link_broken,
+        moved_to,
+    })
 }
 
 #[allow(dead_code)]
@@ -45,7 +261,7 @@ enum DbRequest {
         label: String,
         url: String,
         _timestamp: i64,
-        resp: Sender<anyhow::Result<()>>,
+        resp: Sender<anyhow::Result<i64>>,
     },
     ListRecent {
         limit: i64,
@@ -54,14 +270,122 @@ enum DbRequest {
     ListFavorites {
         resp: Sender<anyhow::Result<Vec<UrlRecord>>>,
     },
+    Update {
+        id: i64,
+        label: Option<String>,
+        url: Option<String>,
+        resp: Sender<anyhow::Result<()>>,
+    },
     Delete {
         id: i64,
         resp: Sender<anyhow::Result<()>>,
     },
+    AddFavorite {
+        url_id: i64,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    RemoveFavorite {
+        url_id: i64,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    SetNoFetch {
+        url_id: i64,
+        no_fetch: bool,
+        resp: Sender<Result<()>>,
+    },
+    IsFetchDisabled {
+        url: String,
+        resp: Sender<Result<bool>>,
+    },
+    RecordLinkCheck {
+        check: LinkCheck,
+        resp: Sender<Result<()>>,
+    },
+    LinkHistory {
+        url_id: i64,
+        limit: i64,
+        resp: Sender<Result<Vec<LinkCheck>>>,
+    },
+    LinksDue {
+        now: i64,
+        max_age_secs: i64,
+        limit: i64,
+        resp: Sender<Result<Vec<DueLink>>>,
+    },
+    DuplicatesOf {
+        url: String,
+        resp: Sender<Result<Vec<UrlRecord>>>,
+    },
+    FindDuplicates {
+        resp: Sender<Result<Vec<DuplicateGroup>>>,
+    },
+    MergeUrls {
+        ids: Vec<i64>,
+        resp: Sender<Result<i64>>,
+    },
+    Subscribe {
+        tx: Sender<DbEvent>,
+    },
+    Shutdown {
+        resp: Sender<Result<()>>,
+    },
+    ListBrokenLinks {
+        resp: Sender<Result<Vec<UrlRecord>>>,
+    },
+    IsFavorite {
+        url_id: i64,
+        resp: Sender<anyhow::Result<bool>>,
+    },
+    TagUrl {
+        url_id: i64,
+        tag: String,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    UntagUrl {
+        url_id: i64,
+        tag: String,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    ListTags {
+        resp: Sender<anyhow::Result<Vec<Tag>>>,
+    },
+    ListByTag {
+        tag: String,
+        resp: Sender<anyhow::Result<Vec<UrlRecord>>>,
+    },
+    RenameTag {
+        from: String,
+        to: String,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    MergeTags {
+        from: String,
+        into: String,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    RecordVisit {
+        url_id: i64,
+        visited_at: i64,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    ListFrecent {
+        limit: i64,
+        now: i64,
+        resp: Sender<anyhow::Result<Vec<UrlRecord>>>,
+    },
+    Search {
+        query: String,
+        limit: i64,
+        resp: Sender<anyhow::Result<Vec<SearchHit>>>,
+    },
     GetById {
         id: i64,
         resp: Sender<anyhow::Result<Option<UrlRecord>>>,
     },
+    Snapshot {
+        include_icons: bool,
+        resp: Sender<anyhow::Result<export::Library>>,
+    },
     UpsertSiteMeta {
         origin: String,
         site_name: Option<String>,
@@ -74,6 +398,39 @@ enum DbRequest {
         origin: String,
         resp: Sender<anyhow::Result<Option<SiteMeta>>>,
     },
+    SetPageDetails {
+        origin: String,
+        details: PageDetails,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    SetPreviewImage {
+        origin: String,
+        mime: Option<String>,
+        data: Option<Vec<u8>>,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    StaleSites {
+        now: i64,
+        max_age_secs: i64,
+        limit: i64,
+        resp: Sender<anyhow::Result<Vec<StaleSite>>>,
+    },
+    RecordFetchSuccess {
+        origin: String,
+        now: i64,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        resp: Sender<anyhow::Result<()>>,
+    },
+    RecordFetchFailure {
+        origin: String,
+        now: i64,
+        error: String,
+        resp: Sender<anyhow::Result<i64>>,
+    },
+    MarkSitesStale {
+        resp: Sender<anyhow::Result<()>>,
+    },
     InsertIcon {
         site_id: i64,
         src_url: String,
@@ -82,7 +439,12 @@ enum DbRequest {
         mime: Option<String>,
         data: Vec<u8>,
         fetched_at: Option<i64>,
-        resp: Sender<anyhow::Result<()>>,
+        resp: Sender<anyhow::Result<i64>>,
+    },
+    BestIcon {
+        site_id: i64,
+        target_px: i64,
+        resp: Sender<anyhow::Result<Option<Icon>>>,
     },
 }
 
@@ -104,7 +466,19 @@ pub fn get_global() -> Option<DbHandle> {
 }
 
 impl DbHandle {
-    pub fn insert_url(&self, label: &str, url: &str, timestamp: i64) -> Result<()> {
+    /// Stop the DB actor and close its connection; returns once it is closed.
+    /// Requests sent afterwards, through any handle, fail.
+    pub fn shutdown(&self) -> Result<()> {
+        let (tx, rx) = unbounded();
+        self.tx
+            .send(DbRequest::Shutdown { resp: tx })
+            .map_err(|e| anyhow!("Failed to send shutdown request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Insert a URL and return its new id.
+    pub fn insert_url(&self, label: &str, url: &str, timestamp: i64) -> Result<i64> {
         let (tx, rx) = unbounded();
         let req = DbRequest::Insert {
             label: label.to_string(),
@@ -141,6 +515,34 @@ impl DbHandle {
             .map_err(|e| anyhow!("DB response recv failed: {}", e))??)
     }
 
+    /// URLs whose latest link check found them broken, most recently checked first.
+    pub fn list_broken_links(&self) -> Result<Vec<UrlRecord>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::ListBrokenLinks { resp: tx };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send list_broken_links request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Change the label and/or URL of an existing row, keeping its id, timestamp
+    /// and favorite mark. `None` leaves the field untouched.
+    pub fn update_url(&self, id: i64, label: Option<&str>, url: Option<&str>) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::Update {
+            id,
+            label: label.map(|s| s.to_string()),
+            url: url.map(|s| s.to_string()),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send update request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
     pub fn delete(&self, id: i64) -> Result<()> {
         let (tx, rx) = unbounded();
         let req = DbRequest::Delete { id, resp: tx };
@@ -151,6 +553,202 @@ impl DbHandle {
             .map_err(|e| anyhow!("DB response recv failed: {}", e))?
     }
 
+    /// Mark a URL as favorite. Marking an existing favorite again is a no-op.
+    pub fn add_favorite(&self, url_id: i64) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::AddFavorite { url_id, resp: tx };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send add_favorite request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    pub fn remove_favorite(&self, url_id: i64) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::RemoveFavorite { url_id, resp: tx };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send remove_favorite request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    pub fn is_favorite(&self, url_id: i64) -> Result<bool> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::IsFavorite { url_id, resp: tx };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send is_favorite request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Opt a URL out of (or back into) metadata fetching.
+    pub fn set_no_fetch(&self, url_id: i64, no_fetch: bool) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::SetNoFetch {
+            url_id,
+            no_fetch,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send set_no_fetch request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Whether a saved row with exactly this URL has fetching turned off.
+    pub fn is_fetch_disabled(&self, url: &str) -> Result<bool> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::IsFetchDisabled {
+            url: url.to_string(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send is_fetch_disabled request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Attach `tag` to a URL, creating the tag if needed. Tag names are trimmed
+    /// and compared case-insensitively.
+    pub fn tag_url(&self, url_id: i64, tag: &str) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::TagUrl {
+            url_id,
+            tag: tag.to_string(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send tag_url request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Detach `tag` from a URL. Tags left without URLs are removed.
+    pub fn untag_url(&self, url_id: i64, tag: &str) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::UntagUrl {
+            url_id,
+            tag: tag.to_string(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send untag_url request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// All tags with their URL counts, sorted by name.
+    pub fn list_tags(&self) -> Result<Vec<Tag>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::ListTags { resp: tx };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send list_tags request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    pub fn list_by_tag(&self, tag: &str) -> Result<Vec<UrlRecord>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::ListByTag {
+            tag: tag.to_string(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send list_by_tag request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Rename a tag. Fails if another tag already uses the new name; use
+    /// `merge_tags` for that.
+    pub fn rename_tag(&self, from: &str, to: &str) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::RenameTag {
+            from: from.to_string(),
+            to: to.to_string(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send rename_tag request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Move every URL tagged `from` to `into` (created if needed) and delete `from`.
+    pub fn merge_tags(&self, from: &str, into: &str) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::MergeTags {
+            from: from.to_string(),
+            into: into.to_string(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send merge_tags request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Record that a URL was opened at `visited_at` (unix seconds).
+    pub fn record_visit(&self, url_id: i64, visited_at: i64) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::RecordVisit {
+            url_id,
+            visited_at,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send record_visit request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// URLs ordered by frecency (frequency and recency of visits), best first.
+    pub fn list_frecent(&self, limit: i64) -> Result<Vec<UrlRecord>> {
+        self.list_frecent_at(limit, chrono::Utc::now().timestamp())
+    }
+
+    fn list_frecent_at(&self, limit: i64, now: i64) -> Result<Vec<UrlRecord>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::ListFrecent {
+            limit,
+            now,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send list_frecent request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Full-text search over labels, URLs, site names and descriptions, best
+    /// matches first. Blank queries return no hits.
+    pub fn search(&self, query: &str, limit: i64) -> Result<Vec<SearchHit>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::Search {
+            query: query.to_string(),
+            limit,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send search request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
     pub fn get_by_id(&self, id: i64) -> Result<Option<UrlRecord>> {
         let (tx, rx) = unbounded();
         let req = DbRequest::GetById { id, resp: tx };
@@ -200,21 +798,129 @@ impl DbHandle {
             .map_err(|e| anyhow!("DB response recv failed: {}", e))??)
     }
 
-    pub fn insert_icon(
-        &self,
-        site_id: i64,
-        src_url: &str,
-        width: Option<i64>,
-        height: Option<i64>,
-        mime: Option<&str>,
-        data: Vec<u8>,
-        fetched_at: Option<i64>,
-    ) -> Result<()> {
+    /// Replace the page details stored for `origin`, creating its row if needed.
+    pub fn set_page_details(&self, origin: &str, details: &PageDetails) -> Result<()> {
         let (tx, rx) = unbounded();
-        let req = DbRequest::InsertIcon {
-            site_id,
-            src_url: src_url.to_string(),
-            width,
+        let req = DbRequest::SetPageDetails {
+            origin: origin.to_string(),
+            details: details.clone(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send set_page_details request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Store (or with `None`, clear) the preview image of `origin`.
+    pub fn set_preview_image(
+        &self,
+        origin: &str,
+        mime: Option<&str>,
+        data: Option<Vec<u8>>,
+    ) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::SetPreviewImage {
+            origin: origin.to_string(),
+            mime: mime.map(|s| s.to_string()),
+            data,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send set_preview_image request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Origins whose metadata is missing or older than `max_age_secs` at `now`
+    /// and not backing off, least recently fetched first. Each comes with its
+    /// oldest URL that isn't opted out of fetching.
+    pub fn stale_sites(&self, now: i64, max_age_secs: i64, limit: usize) -> Result<Vec<StaleSite>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::StaleSites {
+            now,
+            max_age_secs,
+            limit: limit as i64,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send stale_sites request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Mark `origin` as fetched at `now`, keeping the response validators and
+    /// clearing any backoff.
+    pub fn record_fetch_success(
+        &self,
+        origin: &str,
+        now: i64,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+    ) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::RecordFetchSuccess {
+            origin: origin.to_string(),
+            now,
+            etag: etag.map(|s| s.to_string()),
+            last_modified: last_modified.map(|s| s.to_string()),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send record_fetch_success request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Count a failed fetch of `origin` and return when it may be retried.
+    pub fn record_fetch_failure(&self, origin: &str, now: i64, error: &str) -> Result<i64> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::RecordFetchFailure {
+            origin: origin.to_string(),
+            now,
+            error: error.to_string(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send record_fetch_failure request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Make every site due for a refresh now, dropping validators and backoff.
+    pub fn mark_sites_stale(&self) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::MarkSitesStale { resp: tx };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send mark_sites_stale request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Store an icon blob for `site_id` and return its row id. Dimensions are
+    /// decoded from `data` when possible, overriding the declared ones; a blob
+    /// the site already has only refreshes `fetched_at`.
+    pub fn insert_icon(
+        &self,
+        site_id: i64,
+        src_url: &str,
+        width: Option<i64>,
+        height: Option<i64>,
+        mime: Option<&str>,
+        data: Vec<u8>,
+        fetched_at: Option<i64>,
+    ) -> Result<i64> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::InsertIcon {
+            site_id,
+            src_url: src_url.to_string(),
+            width,
             height,
             mime: mime.map(|s| s.to_string()),
             data,
@@ -227,14 +933,94 @@ impl DbHandle {
         rx.recv()
             .map_err(|e| anyhow!("DB response recv failed: {}", e))?
     }
+
+    /// The icon of `site_id` best suited for display at `target_px` pixels:
+    /// the smallest one at least that large, else the largest available.
+    pub fn best_icon(&self, site_id: i64, target_px: u32) -> Result<Option<Icon>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::BestIcon {
+            site_id,
+            target_px: target_px as i64,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send best_icon request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+}
+
+/// Content hash used to deduplicate icon blobs (hex SHA-256).
+fn icon_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
 }
 
-/// Initialize the DB actor: spawn a dedicated thread owning the Connection and return a `DbHandle`.
-pub fn init_db() -> Result<DbHandle> {
+/// Pixel size of an icon blob, if the `image` crate can read its header.
+fn icon_dimensions(data: &[u8]) -> Option<(i64, i64)> {
+    let (w, h) = image::ImageReader::new(std::io::Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()?;
+    Some((w as i64, h as i64))
+}
+
+/// Environment variable overriding the database location (a file path or `:memory:`).
+pub const DB_PATH_ENV: &str = "RUSTINE_DB_PATH";
+
+const IN_MEMORY: &str = ":memory:";
+
+/// Resolve where the database lives: `RUSTINE_DB_PATH` when set, otherwise
+/// `rustine.db` inside the per-user data directory (`$XDG_DATA_HOME/rustine`,
+/// `~/Library/Application Support/Rustine`, `%APPDATA%\Rustine`).
+pub fn resolve_db_path() -> Result<PathBuf> {
+    db_path_from(std::env::var_os(DB_PATH_ENV), dirs::data_dir())
+}
+
+fn db_path_from(env_override: Option<OsString>, data_dir: Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(p) = env_override.filter(|p| !p.is_empty()) {
+        return Ok(PathBuf::from(p));
+    }
+    let base = data_dir.ok_or_else(|| {
+        anyhow!(
+            "could not determine the user data directory; set {} instead",
+            DB_PATH_ENV
+        )
+    })?;
+    Ok(app_dir(base).join("rustine.db"))
+}
+
+/// The application's directory inside the per-user data directory.
+pub(crate) fn app_dir(data_dir: PathBuf) -> PathBuf {
+    if cfg!(target_os = "linux") {
+        data_dir.join("rustine")
+    } else {
+        data_dir.join("Rustine")
+    }
+}
+
+/// Initialize the DB actor: open the database at `path` (creating missing parent
+/// directories; `:memory:` opens a private in-memory DB), bring its schema up to
+/// date, then spawn a dedicated thread owning the Connection and return a `DbHandle`.
+pub fn init_db(path: &Path) -> Result<DbHandle> {
+    let mut conn = if path.as_os_str() == IN_MEMORY {
+        Connection::open_in_memory()?
+    } else {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| {
+                anyhow!("failed to create DB directory {}: {}", parent.display(), e)
+            })?;
+        }
+        Connection::open(path)
+            .map_err(|e| anyhow!("failed to open DB at {}: {}", path.display(), e))?
+    };
+    migrations::run(&mut conn)?;
+
     let (tx, rx): (Sender<DbRequest>, Receiver<DbRequest>) = unbounded();
 
     thread::spawn(move || {
-        if let Err(e) = db_thread(rx) {
+        if let Err(e) = db_thread(conn, rx) {
             eprintln!("DB thread error: {}", e);
         }
     });
@@ -242,128 +1028,321 @@ pub fn init_db() -> Result<DbHandle> {
     Ok(DbHandle { tx })
 }
 
-fn db_thread(rx: Receiver<DbRequest>) -> Result<()> {
-    let conn = Connection::open("rustine.db")?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS urls (
-            id INTEGER PRIMARY KEY,
-            label TEXT NOT NULL,
-            url TEXT NOT NULL,
-            timestamp INTEGER NOT NULL
-        )",
-        params![],
-    )?;
-
-    // Table to mark favourite URLs
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS favorites (
-            url_id INTEGER PRIMARY KEY,
-            added_at INTEGER NOT NULL
-        )",
-        params![],
-    )?;
-    // New tables for site metadata and icons
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS site_meta (
-            id INTEGER PRIMARY KEY,
-            origin TEXT UNIQUE NOT NULL,
-            site_name TEXT,
-            description TEXT,
-            manifest_url TEXT,
-            metadata_fetched_at INTEGER
-        )",
-        params![],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS icons (
-            id INTEGER PRIMARY KEY,
-            site_id INTEGER NOT NULL,
-            src_url TEXT NOT NULL,
-            width INTEGER,
-            height INTEGER,
-            mime TEXT,
-            data BLOB NOT NULL,
-            fetched_at INTEGER,
-            FOREIGN KEY(site_id) REFERENCES site_meta(id)
-        )",
-        params![],
-    )?;
-
+fn db_thread(conn: Connection, rx: Receiver<DbRequest>) -> Result<()> {
+    let mut subscribers: Vec<Sender<DbEvent>> = Vec::new();
+    let mut shutdown = None;
     while let Ok(req) = rx.recv() {
+        // what the request changed, published once it succeeded
+        let mut events = Vec::new();
         match req {
+            DbRequest::Subscribe { tx } => subscribers.push(tx),
+            DbRequest::Shutdown { resp } => {
+                shutdown = Some(resp);
+                break;
+            }
             DbRequest::Insert {
                 label,
                 url,
                 _timestamp,
                 resp,
             } => {
-                let res = (|| -> Result<()> {
+                let res = (|| -> Result<i64> {
                     conn.execute(
-                        "INSERT INTO urls (label, url, timestamp) VALUES (?1, ?2, ?3)",
-                        params![label, url, _timestamp],
+                        "INSERT INTO urls (label, url, timestamp, origin, canonical)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![
+                            label,
+                            url,
+                            _timestamp,
+                            crate::urlnorm::origin(&url),
+                            crate::urlnorm::canonical(&url)
+                        ],
                     )?;
-                    Ok(())
+                    Ok(conn.last_insert_rowid())
                 })();
+                if let Ok(id) = res {
+                    events.push(DbEvent::UrlInserted { id });
+                }
                 let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
             }
             DbRequest::ListRecent { limit, resp } => {
                 let res = (|| -> Result<Vec<UrlRecord>> {
-                    let mut stmt = conn.prepare(
-                        "SELECT u.id, u.label, u.url, u.timestamp, m.site_name, ic.mime, ic.data 
-                         FROM urls u 
-                         LEFT JOIN site_meta m ON m.origin = (SELECT substr(u.url, instr(u.url, '://') + 3, instr(substr(u.url, instr(u.url, '://') + 3), '/') - 1))
-                         LEFT JOIN icons ic ON ic.site_id = m.id AND ic.fetched_at = (
-                             SELECT MAX(fetched_at) FROM icons WHERE site_id = m.id
-                         )
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT {}
+                         FROM urls u
+                         {}
                          ORDER BY u.timestamp DESC LIMIT ?1",
-                    )?;
+                        URL_RECORD_COLUMNS, URL_RECORD_JOINS
+                    ))?;
                     let rows = stmt
-                        .query_map(params![limit], |row| {
-                            Ok(UrlRecord {
-                                id: row.get(0)?,
-                                label: row.get(1)?,
-                                url: row.get(2)?,
-                                _timestamp: row.get(3)?,
-                                site_name: row.get(4)?,
-                                icon_mime: row.get(5)?,
-                                icon_data: row.get(6)?,
-                            })
-                        })?
+                        .query_map(params![limit], url_record_from_row)?
                         .collect::<Result<Vec<_>, rusqlite::Error>>()?;
                     Ok(rows)
                 })();
                 let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
             }
+            DbRequest::Update {
+                id,
+                label,
+                url,
+                resp,
+            } => {
+                let res = (|| -> Result<()> {
+                    let changed = conn.execute(
+                        "UPDATE urls SET label = COALESCE(?2, label), url = COALESCE(?3, url),
+                         origin = CASE WHEN ?3 IS NULL THEN origin ELSE ?4 END,
+                         canonical = CASE WHEN ?3 IS NULL THEN canonical ELSE ?5 END
+                         WHERE id = ?1",
+                        params![
+                            id,
+                            label,
+                            url,
+                            url.as_deref().and_then(crate::urlnorm::origin),
+                            url.as_deref().and_then(crate::urlnorm::canonical)
+                        ],
+                    )?;
+                    if changed == 0 {
+                        return Err(anyhow!("no url with id {}", id));
+                    }
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::UrlUpdated { id });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
             DbRequest::Delete { id, resp } => {
                 let res = (|| -> Result<()> {
-                    conn.execute("DELETE FROM urls WHERE id = ?1", params![id])?;
+                    // all or nothing: never leave a row that lost its favorite and tags
+                    let tx = conn.unchecked_transaction()?;
+                    delete_url(&tx, id)?;
+                    tx.commit()?;
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::UrlDeleted { id });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::AddFavorite { url_id, resp } => {
+                let res = (|| -> Result<()> {
+                    let inserted = conn.execute(
+                        "INSERT OR IGNORE INTO favorites (url_id, added_at)
+                         SELECT id, ?2 FROM urls WHERE id = ?1",
+                        params![url_id, chrono::Utc::now().timestamp()],
+                    )?;
+                    if inserted == 0 {
+                        let exists: bool = conn.query_row(
+                            "SELECT EXISTS(SELECT 1 FROM urls WHERE id = ?1)",
+                            params![url_id],
+                            |r| r.get(0),
+                        )?;
+                        if !exists {
+                            return Err(anyhow!("no url with id {}", url_id));
+                        }
+                    }
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::FavoriteChanged {
+                        id: url_id,
+                        favorite: true,
+                    });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::RemoveFavorite { url_id, resp } => {
+                let res = (|| -> Result<()> {
+                    conn.execute("DELETE FROM favorites WHERE url_id = ?1", params![url_id])?;
                     Ok(())
                 })();
+                if res.is_ok() {
+                    events.push(DbEvent::FavoriteChanged {
+                        id: url_id,
+                        favorite: false,
+                    });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::SetNoFetch {
+                url_id,
+                no_fetch,
+                resp,
+            } => {
+                let res = (|| -> Result<()> {
+                    let changed = conn.execute(
+                        "UPDATE urls SET no_fetch = ?1 WHERE id = ?2",
+                        params![no_fetch, url_id],
+                    )?;
+                    if changed == 0 {
+                        return Err(anyhow!("no url with id {}", url_id));
+                    }
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::UrlUpdated { id: url_id });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::IsFetchDisabled { url, resp } => {
+                let res = (|| -> Result<bool> {
+                    let disabled: bool = conn.query_row(
+                        "SELECT EXISTS(SELECT 1 FROM urls WHERE url = ?1 AND no_fetch = 1)",
+                        params![url],
+                        |r| r.get(0),
+                    )?;
+                    Ok(disabled)
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::IsFavorite { url_id, resp } => {
+                let res = (|| -> Result<bool> {
+                    let fav: bool = conn.query_row(
+                        "SELECT EXISTS(SELECT 1 FROM favorites WHERE url_id = ?1)",
+                        params![url_id],
+                        |r| r.get(0),
+                    )?;
+                    Ok(fav)
+                })();
                 let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
             }
             DbRequest::ListFavorites { resp } => {
                 let res = (|| -> Result<Vec<UrlRecord>> {
-                    let mut stmt = conn.prepare(
-                        "SELECT u.id, u.label, u.url, u.timestamp, m.site_name, ic.mime, ic.data
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT {}
                          FROM urls u
                          INNER JOIN favorites f ON f.url_id = u.id
-                         LEFT JOIN site_meta m ON m.origin = (SELECT substr(u.url, instr(u.url, '://') + 3, instr(substr(u.url, instr(u.url, '://') + 3), '/') - 1))
-                         LEFT JOIN icons ic ON ic.site_id = m.id AND ic.fetched_at = (
-                             SELECT MAX(fetched_at) FROM icons WHERE site_id = m.id
-                         )
+                         {}
                          ORDER BY f.added_at DESC",
+                        URL_RECORD_COLUMNS, URL_RECORD_JOINS
+                    ))?;
+                    let rows = stmt
+                        .query_map(params![], url_record_from_row)?
+                        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+                    Ok(rows)
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::ListBrokenLinks { resp } => {
+                let res = (|| -> Result<Vec<UrlRecord>> {
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT {}
+                         FROM urls u
+                         {}
+                         WHERE {}
+                         ORDER BY lc.checked_at DESC, u.id",
+                        URL_RECORD_COLUMNS,
+                        URL_RECORD_JOINS,
+                        links::LATEST_CHECK_BROKEN
+                    ))?;
+                    let rows = stmt
+                        .query_map(params![], url_record_from_row)?
+                        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+                    Ok(rows)
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::RecordLinkCheck { check, resp } => {
+                let res = links::record(&conn, &check);
+                if res.is_ok() {
+                    events.push(DbEvent::UrlUpdated { id: check.url_id });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::LinkHistory {
+                url_id,
+                limit,
+                resp,
+            } => {
+                let res = links::history(&conn, url_id, limit);
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::LinksDue {
+                now,
+                max_age_secs,
+                limit,
+                resp,
+            } => {
+                let res = links::due(&conn, now, max_age_secs, limit);
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::DuplicatesOf { url, resp } => {
+                let res = dedup::duplicates_of(&conn, &url);
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::FindDuplicates { resp } => {
+                let res = dedup::find(&conn);
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::MergeUrls { ids, resp } => {
+                let res = dedup::merge(&conn, &ids);
+                if let Ok(keep) = res {
+                    events.extend(
+                        ids.iter()
+                            .filter(|id| **id != keep)
+                            .map(|&id| DbEvent::UrlDeleted { id }),
+                    );
+                    events.push(DbEvent::UrlUpdated { id: keep });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::TagUrl { url_id, tag, resp } => {
+                let res = (|| -> Result<()> {
+                    let exists: bool = conn.query_row(
+                        "SELECT EXISTS(SELECT 1 FROM urls WHERE id = ?1)",
+                        params![url_id],
+                        |r| r.get(0),
+                    )?;
+                    if !exists {
+                        return Err(anyhow!("no url with id {}", url_id));
+                    }
+                    let tag_id = ensure_tag(&conn, &tag)?;
+                    conn.execute(
+                        "INSERT OR IGNORE INTO url_tags (url_id, tag_id) VALUES (?1, ?2)",
+                        params![url_id, tag_id],
+                    )?;
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::UrlUpdated { id: url_id });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::UntagUrl { url_id, tag, resp } => {
+                let res = (|| -> Result<()> {
+                    if let Some(tag_id) = find_tag(&conn, &tag)? {
+                        conn.execute(
+                            "DELETE FROM url_tags WHERE url_id = ?1 AND tag_id = ?2",
+                            params![url_id, tag_id],
+                        )?;
+                        conn.execute(
+                            "DELETE FROM tags WHERE id = ?1
+                             AND NOT EXISTS(SELECT 1 FROM url_tags WHERE tag_id = ?1)",
+                            params![tag_id],
+                        )?;
+                    }
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::UrlUpdated { id: url_id });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::ListTags { resp } => {
+                let res = (|| -> Result<Vec<Tag>> {
+                    let mut stmt = conn.prepare(
+                        "SELECT t.id, t.name, COUNT(ut.url_id)
+                         FROM tags t
+                         LEFT JOIN url_tags ut ON ut.tag_id = t.id
+                         GROUP BY t.id
+                         ORDER BY t.name COLLATE NOCASE",
                     )?;
                     let rows = stmt
                         .query_map(params![], |row| {
-                            Ok(UrlRecord {
+                            Ok(Tag {
                                 id: row.get(0)?,
-                                label: row.get(1)?,
-                                url: row.get(2)?,
-                                _timestamp: row.get(3)?,
-                                site_name: row.get(4)?,
-                                icon_mime: row.get(5)?,
-                                icon_data: row.get(6)?,
+                                name: row.get(1)?,
+                                url_count: row.get(2)?,
                             })
                         })?
                         .collect::<Result<Vec<_>, rusqlite::Error>>()?;
@@ -371,28 +1350,161 @@ fn db_thread(rx: Receiver<DbRequest>) -> Result<()> {
                 })();
                 let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
             }
+            DbRequest::ListByTag { tag, resp } => {
+                let res = (|| -> Result<Vec<UrlRecord>> {
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT {}
+                         FROM urls u
+                         INNER JOIN url_tags bt ON bt.url_id = u.id
+                         INNER JOIN tags btt ON btt.id = bt.tag_id AND btt.name = ?1
+                         {}
+                         ORDER BY u.label COLLATE NOCASE",
+                        URL_RECORD_COLUMNS, URL_RECORD_JOINS
+                    ))?;
+                    let rows = stmt
+                        .query_map(params![tag.trim()], url_record_from_row)?
+                        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+                    Ok(rows)
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::RenameTag { from, to, resp } => {
+                let res = (|| -> Result<()> {
+                    let to = tag_name(&to)?;
+                    let from_id =
+                        find_tag(&conn, &from)?.ok_or_else(|| anyhow!("no tag named {}", from))?;
+                    if let Some(existing) = find_tag(&conn, &to)? {
+                        if existing != from_id {
+                            return Err(anyhow!("tag {} already exists; merge instead", to));
+                        }
+                    }
+                    conn.execute(
+                        "UPDATE tags SET name = ?1 WHERE id = ?2",
+                        params![to, from_id],
+                    )?;
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::TagsChanged);
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::MergeTags { from, into, resp } => {
+                let res = (|| -> Result<()> {
+                    let from_id =
+                        find_tag(&conn, &from)?.ok_or_else(|| anyhow!("no tag named {}", from))?;
+                    let tx = conn.unchecked_transaction()?;
+                    let into_id = ensure_tag(&tx, &into)?;
+                    if into_id == from_id {
+                        return Ok(());
+                    }
+                    tx.execute(
+                        "INSERT OR IGNORE INTO url_tags (url_id, tag_id)
+                         SELECT url_id, ?2 FROM url_tags WHERE tag_id = ?1",
+                        params![from_id, into_id],
+                    )?;
+                    tx.execute("DELETE FROM url_tags WHERE tag_id = ?1", params![from_id])?;
+                    tx.execute("DELETE FROM tags WHERE id = ?1", params![from_id])?;
+                    tx.commit()?;
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::TagsChanged);
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::RecordVisit {
+                url_id,
+                visited_at,
+                resp,
+            } => {
+                let res = (|| -> Result<()> {
+                    let inserted = conn.execute(
+                        "INSERT INTO visits (url_id, visited_at) SELECT id, ?2 FROM urls WHERE id = ?1",
+                        params![url_id, visited_at],
+                    )?;
+                    if inserted == 0 {
+                        return Err(anyhow!("no url with id {}", url_id));
+                    }
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::UrlVisited { id: url_id });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::ListFrecent { limit, now, resp } => {
+                let res = (|| -> Result<Vec<UrlRecord>> {
+                    let mut stmt = conn.prepare(&format!(
+                        "{}
+                         SELECT {}
+                         FROM urls u
+                         INNER JOIN scores s ON s.url_id = u.id
+                         {}
+                         ORDER BY s.frecency DESC, u.timestamp DESC
+                         LIMIT ?2",
+                        FRECENCY_SCORES, URL_RECORD_COLUMNS, URL_RECORD_JOINS
+                    ))?;
+                    let rows = stmt
+                        .query_map(params![now, limit], url_record_from_row)?
+                        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+                    Ok(rows)
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::Search { query, limit, resp } => {
+                let res = (|| -> Result<Vec<SearchHit>> {
+                    let fts = match fts_query(&query) {
+                        Some(q) => q,
+                        None => return Ok(Vec::new()),
+                    };
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT {}, snippet(urls_fts, -1, ?2, ?3, '…', 12)
+                         FROM urls_fts
+                         JOIN urls u ON u.id = urls_fts.rowid
+                         {}
+                         WHERE urls_fts MATCH ?1
+                         ORDER BY bm25(urls_fts, 10.0, 5.0, 3.0, 1.0)
+                         LIMIT ?4",
+                        URL_RECORD_COLUMNS, URL_RECORD_JOINS
+                    ))?;
+                    let rows = stmt
+                        .query_map(
+                            params![
+                                fts,
+                                SNIPPET_OPEN.to_string(),
+                                SNIPPET_CLOSE.to_string(),
+                                limit
+                            ],
+                            |row| {
+                                Ok(SearchHit {
+                                    record: url_record_from_row(row)?,
+                                    snippet: row.get(13)?,
+                                })
+                            },
+                        )?
+                        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+                    Ok(rows)
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::Snapshot {
+                include_icons,
+                resp,
+            } => {
+                let res = export::snapshot(&conn, include_icons);
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
             DbRequest::GetById { id, resp } => {
                 let res = (|| -> Result<Option<UrlRecord>> {
-                    let mut stmt = conn.prepare(
-                        "SELECT u.id, u.label, u.url, u.timestamp, m.site_name, ic.mime, ic.data 
-                         FROM urls u 
-                         LEFT JOIN site_meta m ON m.origin = (SELECT substr(u.url, instr(u.url, '://') + 3, instr(substr(u.url, instr(u.url, '://') + 3), '/') - 1))
-                         LEFT JOIN icons ic ON ic.site_id = m.id AND ic.fetched_at = (
-                             SELECT MAX(fetched_at) FROM icons WHERE site_id = m.id
-                         )
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT {}
+                         FROM urls u
+                         {}
                          WHERE u.id = ?1 LIMIT 1",
-                    )?;
-                    let mut rows = stmt.query_map(params![id], |row| {
-                        Ok(UrlRecord {
-                            id: row.get(0)?,
-                            label: row.get(1)?,
-                            url: row.get(2)?,
-                            _timestamp: row.get(3)?,
-                            site_name: row.get(4)?,
-                            icon_mime: row.get(5)?,
-                            icon_data: row.get(6)?,
-                        })
-                    })?;
+                        URL_RECORD_COLUMNS, URL_RECORD_JOINS
+                    ))?;
+                    let mut rows = stmt.query_map(params![id], url_record_from_row)?;
                     if let Some(r) = rows.next() {
                         Ok(Some(r?))
                     } else {
@@ -418,13 +1530,20 @@ fn db_thread(rx: Receiver<DbRequest>) -> Result<()> {
                     )?;
                     Ok(())
                 })();
+                if res.is_ok() {
+                    events.push(DbEvent::SiteMetaUpdated { origin });
+                }
                 let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
             }
             DbRequest::GetSiteMetaByOrigin { origin, resp } => {
                 let res = (|| -> Result<Option<SiteMeta>> {
-                    let mut stmt = conn.prepare(
-                        "SELECT id, origin, site_name, description, manifest_url, metadata_fetched_at FROM site_meta WHERE origin = ?1 LIMIT 1",
-                    )?;
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT id, origin, site_name, description, manifest_url, metadata_fetched_at,
+                            preview_mime, preview_data, etag, last_modified, fetch_failures,
+                            next_fetch_at, last_fetch_error, {}
+                         FROM site_meta WHERE origin = ?1 LIMIT 1",
+                        PAGE_DETAILS_COLUMNS
+                    ))?;
                     let mut rows = stmt.query_map(params![origin], |row| {
                         Ok(SiteMeta {
                             id: row.get(0)?,
@@ -433,6 +1552,14 @@ fn db_thread(rx: Receiver<DbRequest>) -> Result<()> {
                             description: row.get(3)?,
                             manifest_url: row.get(4)?,
                             metadata_fetched_at: row.get(5)?,
+                            preview_mime: row.get(6)?,
+                            preview_data: row.get(7)?,
+                            etag: row.get(8)?,
+                            last_modified: row.get(9)?,
+                            fetch_failures: row.get(10)?,
+                            next_fetch_at: row.get(11)?,
+                            last_fetch_error: row.get(12)?,
+                            page: page_details_from_row(row, 13)?,
                         })
                     })?;
                     if let Some(r) = rows.next() {
@@ -443,6 +1570,153 @@ fn db_thread(rx: Receiver<DbRequest>) -> Result<()> {
                 })();
                 let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
             }
+            DbRequest::SetPageDetails {
+                origin,
+                details,
+                resp,
+            } => {
+                let res = (|| -> Result<()> {
+                    conn.execute(
+                        "INSERT INTO site_meta (origin) VALUES (?1) ON CONFLICT(origin) DO NOTHING",
+                        params![origin],
+                    )?;
+                    conn.execute(
+                        "UPDATE site_meta SET title = ?2, og_site_name = ?3, og_title = ?4,
+                            og_description = ?5, og_image = ?6, twitter_title = ?7,
+                            twitter_description = ?8, twitter_image = ?9,
+                            application_name = ?10, theme_color = ?11
+                         WHERE origin = ?1",
+                        params![
+                            origin,
+                            details.title,
+                            details.og_site_name,
+                            details.og_title,
+                            details.og_description,
+                            details.og_image,
+                            details.twitter_title,
+                            details.twitter_description,
+                            details.twitter_image,
+                            details.application_name,
+                            details.theme_color
+                        ],
+                    )?;
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::SiteMetaUpdated { origin });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::SetPreviewImage {
+                origin,
+                mime,
+                data,
+                resp,
+            } => {
+                let res = (|| -> Result<()> {
+                    conn.execute(
+                        "INSERT INTO site_meta (origin) VALUES (?1) ON CONFLICT(origin) DO NOTHING",
+                        params![origin],
+                    )?;
+                    conn.execute(
+                        "UPDATE site_meta SET preview_mime = ?2, preview_data = ?3 WHERE origin = ?1",
+                        params![origin, mime, data],
+                    )?;
+                    Ok(())
+                })();
+                if res.is_ok() {
+                    events.push(DbEvent::SiteMetaUpdated { origin });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::StaleSites {
+                now,
+                max_age_secs,
+                limit,
+                resp,
+            } => {
+                let res = (|| -> Result<Vec<StaleSite>> {
+                    let mut stmt = conn.prepare(
+                        "SELECT u.origin, u.url FROM urls u
+                         LEFT JOIN site_meta m ON m.origin = u.origin
+                         WHERE u.origin IS NOT NULL
+                           AND u.id = (SELECT MIN(id) FROM urls WHERE origin = u.origin AND no_fetch = 0)
+                           AND (m.metadata_fetched_at IS NULL OR m.metadata_fetched_at <= ?1 - ?2)
+                           AND (m.next_fetch_at IS NULL OR m.next_fetch_at <= ?1)
+                         ORDER BY COALESCE(m.metadata_fetched_at, 0), u.id
+                         LIMIT ?3",
+                    )?;
+                    let rows = stmt
+                        .query_map(params![now, max_age_secs, limit], |r| {
+                            Ok(StaleSite {
+                                origin: r.get(0)?,
+                                url: r.get(1)?,
+                            })
+                        })?
+                        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+                    Ok(rows)
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::RecordFetchSuccess {
+                origin,
+                now,
+                etag,
+                last_modified,
+                resp,
+            } => {
+                let res = (|| -> Result<()> {
+                    conn.execute(
+                        "INSERT INTO site_meta (origin) VALUES (?1) ON CONFLICT(origin) DO NOTHING",
+                        params![origin],
+                    )?;
+                    conn.execute(
+                        "UPDATE site_meta SET metadata_fetched_at = ?2, etag = ?3, last_modified = ?4,
+                            fetch_failures = 0, next_fetch_at = NULL, last_fetch_error = NULL
+                         WHERE origin = ?1",
+                        params![origin, now, etag, last_modified],
+                    )?;
+                    Ok(())
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::RecordFetchFailure {
+                origin,
+                now,
+                error,
+                resp,
+            } => {
+                let res = (|| -> Result<i64> {
+                    conn.execute(
+                        "INSERT INTO site_meta (origin) VALUES (?1) ON CONFLICT(origin) DO NOTHING",
+                        params![origin],
+                    )?;
+                    let failures: i64 = conn.query_row(
+                        "UPDATE site_meta SET fetch_failures = fetch_failures + 1, last_fetch_error = ?2
+                         WHERE origin = ?1 RETURNING fetch_failures",
+                        params![origin, error],
+                        |r| r.get(0),
+                    )?;
+                    let next = now + fetch_backoff_secs(failures);
+                    conn.execute(
+                        "UPDATE site_meta SET next_fetch_at = ?2 WHERE origin = ?1",
+                        params![origin, next],
+                    )?;
+                    Ok(next)
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::MarkSitesStale { resp } => {
+                let res = (|| -> Result<()> {
+                    conn.execute(
+                        "UPDATE site_meta SET metadata_fetched_at = NULL, etag = NULL,
+                            last_modified = NULL, fetch_failures = 0, next_fetch_at = NULL",
+                        params![],
+                    )?;
+                    Ok(())
+                })();
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
             DbRequest::InsertIcon {
                 site_id,
                 src_url,
@@ -453,21 +1727,160 @@ fn db_thread(rx: Receiver<DbRequest>) -> Result<()> {
                 fetched_at,
                 resp,
             } => {
-                let res = (|| -> Result<()> {
-                    conn.execute(
-                        "INSERT INTO icons (site_id, src_url, width, height, mime, data, fetched_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-                        params![site_id, src_url, width, height, mime, data, fetched_at],
+                let res = (|| -> Result<i64> {
+                    let (width, height) = match icon_dimensions(&data) {
+                        Some((w, h)) => (Some(w), Some(h)),
+                        None => (width, height),
+                    };
+                    let hash = icon_hash(&data);
+                    let id = conn.query_row(
+                        "INSERT INTO icons (site_id, src_url, width, height, mime, data, fetched_at, content_hash)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                         ON CONFLICT(site_id, content_hash) DO UPDATE
+                         SET fetched_at = COALESCE(excluded.fetched_at, icons.fetched_at)
+                         RETURNING id",
+                        params![
+                            site_id,
+                            src_url,
+                            width,
+                            height,
+                            mime,
+                            data,
+                            fetched_at,
+                            hash
+                        ],
+                        |r| r.get(0),
                     )?;
-                    Ok(())
+                    Ok(id)
+                })();
+                if let Ok(icon_id) = res {
+                    events.push(DbEvent::IconAdded { site_id, icon_id });
+                }
+                let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
+            }
+            DbRequest::BestIcon {
+                site_id,
+                target_px,
+                resp,
+            } => {
+                let res = (|| -> Result<Option<Icon>> {
+                    let mut stmt = conn.prepare(concat!(
+                        "SELECT id, site_id, src_url, width, height, mime, data, fetched_at
+                         FROM icons WHERE site_id = ?1 ORDER BY ",
+                        best_icon_order!("?2"),
+                        " LIMIT 1"
+                    ))?;
+                    let mut rows = stmt.query_map(params![site_id, target_px], |r| {
+                        Ok(Icon {
+                            id: r.get(0)?,
+                            site_id: r.get(1)?,
+                            src_url: r.get(2)?,
+                            width: r.get(3)?,
+                            height: r.get(4)?,
+                            mime: r.get(5)?,
+                            data: r.get(6)?,
+                            fetched_at: r.get(7)?,
+                        })
+                    })?;
+                    match rows.next() {
+                        Some(icon) => Ok(Some(icon?)),
+                        None => Ok(None),
+                    }
                 })();
                 let _ = resp.send(res.map_err(|e| anyhow!(e.to_string())));
             }
         }
+        if !events.is_empty() {
+            events::publish(&mut subscribers, events);
+        }
     }
 
+    // Ends every subscription, then closes the connection: anything still
+    // pending in SQLite is flushed before `shutdown` returns.
+    drop(subscribers);
+    let res = conn.close().map_err(|(_, e)| anyhow!(e.to_string()));
+    if let Some(resp) = shutdown {
+        let _ = resp.send(res);
+        return Ok(());
+    }
+    res
+}
+
+/// Frecency score per URL (`url_id`, `frecency`), Firefox style: the insertion
+/// and every visit count as events; the 10 most recent events are weighted by
+/// age (<=4 days: 100, <=14: 70, <=31: 50, <=90: 30, older: 10) and the
+/// average weight is multiplied by the total number of events. `?1` is "now".
+const FRECENCY_SCORES: &str = "WITH events AS (
+        SELECT id AS url_id, timestamp AS at FROM urls
+        UNION ALL
+        SELECT url_id, visited_at FROM visits
+    ),
+    weighted AS (
+        SELECT url_id,
+            CASE
+                WHEN ?1 - at <= 4 * 86400 THEN 100
+                WHEN ?1 - at <= 14 * 86400 THEN 70
+                WHEN ?1 - at <= 31 * 86400 THEN 50
+                WHEN ?1 - at <= 90 * 86400 THEN 30
+                ELSE 10
+            END AS weight,
+            ROW_NUMBER() OVER (PARTITION BY url_id ORDER BY at DESC) AS rn,
+            COUNT(*) OVER (PARTITION BY url_id) AS total
+        FROM events
+    ),
+    scores AS (
+        SELECT url_id, MAX(total) * AVG(weight) AS frecency
+        FROM weighted WHERE rn <= 10 GROUP BY url_id
+    )";
+
+/// Delete a URL with its favorite, tags, visits and link checks; tags left
+/// without URLs go too.
+fn delete_url(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM favorites WHERE url_id = ?1", params![id])?;
+    conn.execute("DELETE FROM url_tags WHERE url_id = ?1", params![id])?;
+    conn.execute("DELETE FROM visits WHERE url_id = ?1", params![id])?;
+    conn.execute("DELETE FROM link_checks WHERE url_id = ?1", params![id])?;
+    conn.execute(
+        "DELETE FROM tags WHERE NOT EXISTS(SELECT 1 FROM url_tags WHERE tag_id = tags.id)",
+        params![],
+    )?;
+    conn.execute("DELETE FROM urls WHERE id = ?1", params![id])?;
     Ok(())
 }
 
+/// Validate and trim a user-supplied tag name.
+fn tag_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("tag name cannot be empty"));
+    }
+    if name.contains(TAG_SEPARATOR) {
+        return Err(anyhow!("tag name contains an invalid character"));
+    }
+    Ok(name.to_string())
+}
+
+fn find_tag(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    let mut stmt = conn.prepare("SELECT id FROM tags WHERE name = ?1")?;
+    let mut rows = stmt.query_map(params![name.trim()], |r| r.get(0))?;
+    match rows.next() {
+        Some(id) => Ok(Some(id?)),
+        None => Ok(None),
+    }
+}
+
+fn ensure_tag(conn: &Connection, name: &str) -> Result<i64> {
+    let name = tag_name(name)?;
+    if let Some(id) = find_tag(conn, &name)? {
+        return Ok(id);
+    }
+    conn.execute(
+        "INSERT INTO tags (name, created_at) VALUES (?1, ?2)",
+        params![name, chrono::Utc::now().timestamp()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -541,7 +1954,7 @@ mod tests {
     #[test]
     fn test_db_insert_and_list_recent_basic() -> Result<()> {
         // Start DB actor; insert a URL and verify it's retrievable via list_recent
-        let db = init_db()?;
+        let db = init_db(Path::new(":memory:"))?;
         use std::time::{SystemTime, UNIX_EPOCH};
         let ts = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -556,4 +1969,429 @@ mod tests {
         assert_eq!(items[0].url, "https://example.com");
         Ok(())
     }
+
+    #[test]
+    fn test_db_path_env_override_wins() -> Result<()> {
+        let p = db_path_from(
+            Some(OsString::from("/tmp/custom.db")),
+            Some(PathBuf::from("/data")),
+        )?;
+        assert_eq!(p, PathBuf::from("/tmp/custom.db"));
+        let p = db_path_from(Some(OsString::from(":memory:")), None)?;
+        assert_eq!(p, PathBuf::from(":memory:"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_db_path_defaults_to_data_dir() -> Result<()> {
+        let p = db_path_from(Some(OsString::new()), Some(PathBuf::from("/data")))?;
+        assert!(p.starts_with("/data"));
+        assert_eq!(p.file_name().and_then(|f| f.to_str()), Some("rustine.db"));
+        assert!(db_path_from(None, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_db_creates_parent_dirs() -> Result<()> {
+        let root = std::env::temp_dir().join(format!(
+            "rustine-test-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let path = root.join("nested").join("rustine.db");
+        let db = init_db(&path)?;
+        db.insert_url("File", "https://example.com", 1)?;
+        assert!(path.exists());
+        assert_eq!(db.list_recent(10)?.len(), 1);
+        drop(db);
+        let _ = fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown_closes_the_db_and_ends_subscriptions() -> Result<()> {
+        let root = std::env::temp_dir().join(format!(
+            "rustine-test-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let path = root.join("rustine.db");
+        let db = init_db(&path)?;
+        let sub = db.subscribe()?;
+        let other = db.clone();
+        db.insert_url("Kept", "https://example.com", 1)?;
+
+        db.shutdown()?;
+        assert!(other.list_recent(10).is_err());
+        assert!(db.shutdown().is_err());
+        let drained: Vec<DbEvent> = sub.receiver().iter().collect();
+        assert_eq!(drained.len(), 1);
+
+        // everything written before the shutdown is on disk
+        let reopened = init_db(&path)?;
+        assert_eq!(reopened.list_recent(10)?[0].label, "Kept");
+        reopened.shutdown()?;
+        let _ = fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_url_partial_keeps_id_and_timestamp() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        db.insert_url("Old", "https://old.example.com/", 42)?;
+        let id = db.list_recent(1)?[0].id;
+
+        db.update_url(id, Some("New"), None)?;
+        let rec = db.get_by_id(id)?.expect("row should still exist");
+        assert_eq!(rec.label, "New");
+        assert_eq!(rec.url, "https://old.example.com/");
+        assert_eq!(rec._timestamp, 42);
+
+        db.update_url(id, None, Some("https://new.example.com/"))?;
+        let rec = db.get_by_id(id)?.expect("row should still exist");
+        assert_eq!(rec.label, "New");
+        assert_eq!(rec.url, "https://new.example.com/");
+
+        assert!(db.update_url(id + 1000, Some("Missing"), None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_favorites_add_remove_and_list() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        db.insert_url("One", "https://one.example.com/", 1)?;
+        db.insert_url("Two", "https://two.example.com/", 2)?;
+        let two = db.list_recent(1)?[0].id;
+
+        assert!(!db.is_favorite(two)?);
+        db.add_favorite(two)?;
+        db.add_favorite(two)?; // idempotent
+        assert!(db.is_favorite(two)?);
+
+        let favs = db.list_favorites()?;
+        assert_eq!(favs.len(), 1);
+        assert_eq!(favs[0].id, two);
+        assert!(favs[0].is_favorite);
+        assert!(db
+            .list_recent(10)?
+            .iter()
+            .any(|r| r.id == two && r.is_favorite));
+
+        db.remove_favorite(two)?;
+        assert!(!db.is_favorite(two)?);
+        assert!(db.list_favorites()?.is_empty());
+
+        assert!(db.add_favorite(two + 1000).is_err());
+
+        // deleting a URL drops its favorite mark
+        db.add_favorite(two)?;
+        db.delete(two)?;
+        assert!(db.list_favorites()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_site_meta_joined_by_normalized_origin() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        db.insert_url("Root", "https://Example.com", 1)?;
+        db.insert_url("Deep", "https://example.com:443/a/b?c=d", 2)?;
+        db.insert_url("Other port", "https://example.com:8443/", 3)?;
+        // stored the way the webview fetcher keys it
+        db.upsert_site_meta("https://example.com:443", Some("Example"), None, None, None)?;
+
+        let items = db.list_recent(10)?;
+        let name_of = |label: &str| {
+            items
+                .iter()
+                .find(|r| r.label == label)
+                .and_then(|r| r.site_name.clone())
+        };
+        assert_eq!(name_of("Root").as_deref(), Some("Example"));
+        assert_eq!(name_of("Deep").as_deref(), Some("Example"));
+        assert_eq!(name_of("Other port"), None);
+
+        // changing the URL moves the row to the new origin
+        let other = items.iter().find(|r| r.label == "Other port").unwrap().id;
+        db.update_url(other, None, Some("https://example.com/x"))?;
+        let rec = db.get_by_id(other)?.unwrap();
+        assert_eq!(rec.site_name.as_deref(), Some("Example"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_icons_dedup_by_content_and_best_size_wins() -> Result<()> {
+        let png = |size: u32| {
+            let mut out = std::io::Cursor::new(Vec::new());
+            image::RgbaImage::new(size, size)
+                .write_to(&mut out, image::ImageFormat::Png)
+                .unwrap();
+            out.into_inner()
+        };
+        let db = init_db(Path::new(":memory:"))?;
+        let id = db.insert_url("Example", "https://example.com/", 1)?;
+        db.upsert_site_meta("https://example.com:443", Some("Example"), None, None, None)?;
+        let site = db
+            .get_site_meta_by_origin("https://example.com:443")?
+            .unwrap()
+            .id;
+
+        // declared sizes are replaced by the decoded ones
+        let big = db.insert_icon(
+            site,
+            "https://example.com/512.png",
+            Some(1),
+            Some(1),
+            None,
+            png(512),
+            Some(10),
+        )?;
+        db.insert_icon(
+            site,
+            "https://example.com/32.png",
+            None,
+            None,
+            None,
+            png(32),
+            Some(10),
+        )?;
+        // a newer but undecodable blob keeps its declared size only
+        db.insert_icon(
+            site,
+            "https://example.com/x.ico",
+            None,
+            None,
+            Some("image/x-icon"),
+            b"not an icon".to_vec(),
+            Some(50),
+        )?;
+        assert_eq!(
+            db.insert_icon(
+                site,
+                "https://example.com/again.png",
+                None,
+                None,
+                None,
+                png(512),
+                Some(20)
+            )?,
+            big
+        );
+
+        let best = |px| -> Result<(String, Option<i64>)> {
+            let icon = db.best_icon(site, px)?.unwrap();
+            Ok((icon.src_url, icon.width))
+        };
+        assert_eq!(
+            best(16)?,
+            ("https://example.com/32.png".to_string(), Some(32))
+        );
+        assert_eq!(
+            best(64)?,
+            ("https://example.com/512.png".to_string(), Some(512))
+        );
+        assert_eq!(
+            best(1024)?,
+            ("https://example.com/512.png".to_string(), Some(512))
+        );
+        assert_eq!(db.best_icon(site + 1, 16)?, None);
+
+        // the list row shows the 16px pick, not the newest blob
+        assert_eq!(db.get_by_id(id)?.unwrap().icon_data, Some(png(32)));
+        // ...unless the site has an SVG, which the webview renders at any size
+        db.insert_icon(
+            site,
+            "https://example.com/x.svg",
+            None,
+            None,
+            Some("image/svg+xml"),
+            b"<svg/>".to_vec(),
+            Some(5),
+        )?;
+        assert_eq!(
+            db.get_by_id(id)?.unwrap().icon_data,
+            Some(b"<svg/>".to_vec())
+        );
+        assert_eq!(best(16)?.1, Some(32));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_sites_and_fetch_backoff() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        db.insert_url("A", "https://a.example/", 1)?;
+        db.insert_url("A deep", "https://a.example/deep", 2)?;
+        db.insert_url("B", "https://b.example/", 3)?;
+        let day = 86400;
+        let now = 100 * day;
+        let origins = |now| -> Result<Vec<String>> {
+            Ok(db
+                .stale_sites(now, 7 * day, 10)?
+                .into_iter()
+                .map(|s| s.origin)
+                .collect())
+        };
+
+        // never fetched: both origins are due, each through its oldest URL
+        let due = db.stale_sites(now, 7 * day, 10)?;
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[0].url, "https://a.example/");
+
+        db.record_fetch_success("https://a.example:443", now, Some("\"v1\""), None)?;
+        assert_eq!(origins(now)?, vec!["https://b.example:443"]);
+        assert_eq!(origins(now + 8 * day)?.len(), 2);
+
+        // failures back off exponentially
+        assert_eq!(
+            db.record_fetch_failure("https://b.example:443", now, "timeout")?,
+            now + 3600
+        );
+        assert_eq!(
+            db.record_fetch_failure("https://b.example:443", now, "timeout")?,
+            now + 7200
+        );
+        assert!(origins(now + 3600)?.is_empty());
+        assert_eq!(origins(now + 7200)?, vec!["https://b.example:443"]);
+        let b = db
+            .get_site_meta_by_origin("https://b.example:443")?
+            .unwrap();
+        assert_eq!(b.fetch_failures, 2);
+        assert_eq!(b.last_fetch_error.as_deref(), Some("timeout"));
+        assert_eq!(fetch_backoff_secs(50), 7 * day);
+
+        // a success clears the backoff; marking stale makes everything due again
+        db.record_fetch_success("https://b.example:443", now, None, None)?;
+        assert_eq!(
+            db.get_site_meta_by_origin("https://b.example:443")?
+                .unwrap()
+                .fetch_failures,
+            0
+        );
+        assert!(origins(now)?.is_empty());
+        db.mark_sites_stale()?;
+        assert_eq!(origins(now)?.len(), 2);
+        assert_eq!(
+            db.get_site_meta_by_origin("https://a.example:443")?
+                .unwrap()
+                .etag,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_ranks_and_highlights() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        db.insert_url("Rust docs", "https://doc.rust-lang.org/", 1)?;
+        db.insert_url("Crates", "https://crates.io/search?q=rust", 2)?;
+        db.insert_url("News", "https://news.example.com/", 3)?;
+        db.upsert_site_meta(
+            "https://news.example.com:443",
+            Some("Daily Planet"),
+            Some("Café news and more"),
+            None,
+            None,
+        )?;
+
+        let hits = db.search("rust", 10)?;
+        assert_eq!(hits.len(), 2);
+        // label matches outrank URL-only matches
+        assert_eq!(hits[0].record.label, "Rust docs");
+        assert!(snippet_segments(&hits[0].snippet)
+            .iter()
+            .any(|(text, hl)| *hl && text.eq_ignore_ascii_case("rust")));
+
+        // site metadata is indexed, prefixes and diacritics are tolerated
+        let hits = db.search("plan cafe", 10)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].record.label, "News");
+
+        // edits and deletes keep the index in sync
+        let news = hits[0].record.id;
+        db.update_url(news, Some("Gazette"), None)?;
+        assert_eq!(db.search("gazette", 10)?.len(), 1);
+        db.delete(news)?;
+        assert!(db.search("gazette", 10)?.is_empty());
+
+        // quotes and blank input are harmless
+        assert!(db.search("\"", 10)?.is_empty());
+        assert!(db.search("   ", 10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_tag_untag_rename_merge() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        db.insert_url("A", "https://a.example.com/", 1)?;
+        db.insert_url("B", "https://b.example.com/", 2)?;
+        let ids: Vec<i64> = db.list_recent(10)?.iter().map(|r| r.id).collect();
+        let (b, a) = (ids[0], ids[1]);
+
+        db.tag_url(a, " Work ")?;
+        db.tag_url(a, "work")?; // same tag, case-insensitive
+        db.tag_url(a, "Dev")?;
+        db.tag_url(b, "dev")?;
+        assert!(db.tag_url(a, "  ").is_err());
+        assert!(db.tag_url(a + b + 1000, "x").is_err());
+
+        let rec = db.get_by_id(a)?.unwrap();
+        assert_eq!(rec.tags, vec!["Dev".to_string(), "Work".to_string()]);
+        let tags = db.list_tags()?;
+        assert_eq!(
+            tags.iter()
+                .map(|t| (t.name.as_str(), t.url_count))
+                .collect::<Vec<_>>(),
+            vec![("Dev", 2), ("Work", 1)]
+        );
+        assert_eq!(db.list_by_tag("DEV")?.len(), 2);
+
+        db.rename_tag("work", "Job")?;
+        assert!(db.rename_tag("Job", "dev").is_err());
+        db.merge_tags("Job", "Dev")?;
+        let tags = db.list_tags()?;
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].url_count, 2);
+
+        db.untag_url(b, "Dev")?;
+        assert_eq!(db.list_by_tag("Dev")?.len(), 1);
+        db.delete(a)?;
+        assert!(db.list_by_tag("Dev")?.is_empty());
+        assert!(db.list_tags()?.is_empty(), "orphaned tags are pruned");
+        Ok(())
+    }
+
+    #[test]
+    fn test_frecency_prefers_frequent_recent_visits() -> Result<()> {
+        const DAY: i64 = 86400;
+        let now = 1_000 * DAY;
+        let db = init_db(Path::new(":memory:"))?;
+        db.insert_url("Old but busy", "https://busy.example.com/", now - 365 * DAY)?;
+        db.insert_url("Fresh", "https://fresh.example.com/", now - DAY)?;
+        db.insert_url("Stale", "https://stale.example.com/", now - 200 * DAY)?;
+        let id_of = |label: &str| -> Result<i64> {
+            Ok(db
+                .list_recent(10)?
+                .iter()
+                .find(|r| r.label == label)
+                .unwrap()
+                .id)
+        };
+        let busy = id_of("Old but busy")?;
+        for i in 0..10 {
+            db.record_visit(busy, now - i * 3600)?;
+        }
+        assert!(db.record_visit(busy + 1000, now).is_err());
+
+        let order: Vec<String> = db
+            .list_frecent_at(10, now)?
+            .into_iter()
+            .map(|r| r.label)
+            .collect();
+        assert_eq!(order, vec!["Old but busy", "Fresh", "Stale"]);
+
+        assert_eq!(db.list_frecent_at(1, now)?.len(), 1);
+
+        db.delete(busy)?;
+        assert_eq!(db.list_frecent_at(10, now)?[0].label, "Fresh");
+        Ok(())
+    }
 }
@@ -0,0 +1,216 @@
+//! Duplicate URLs: rows whose `urls.canonical` key (see
+//! `crate::urlnorm::canonical`) matches, and merging them into one row.
+
+use super::{
+    delete_url, url_record_from_row, DbHandle, DbRequest, UrlRecord, URL_RECORD_COLUMNS,
+    URL_RECORD_JOINS,
+};
+use anyhow::{anyhow, Result};
+use crossbeam_channel::unbounded;
+use rusqlite::{params, Connection};
+
+/// Saved URLs sharing one canonical key, oldest (lowest id) first.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub canonical: String,
+    pub records: Vec<UrlRecord>,
+}
+
+impl DuplicateGroup {
+    pub fn ids(&self) -> Vec<i64> {
+        self.records.iter().map(|r| r.id).collect()
+    }
+}
+
+impl DbHandle {
+    /// Saved URLs that `url` would duplicate, oldest first.
+    pub fn duplicates_of(&self, url: &str) -> Result<Vec<UrlRecord>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::DuplicatesOf {
+            url: url.to_string(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send duplicates_of request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Every group of two or more URLs with the same canonical key.
+    pub fn find_duplicates(&self) -> Result<Vec<DuplicateGroup>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::FindDuplicates { resp: tx };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send find_duplicates request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Merge `ids` into the oldest of them (lowest id) and return its id. The
+    /// kept row gains the others' favorite, tags and visits (and their
+    /// metadata opt-out); the others are deleted.
+    pub fn merge_urls(&self, ids: &[i64]) -> Result<i64> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::MergeUrls {
+            ids: ids.to_vec(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send merge_urls request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+}
+
+fn by_canonical(conn: &Connection, canonical: &str) -> Result<Vec<UrlRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM urls u
+         {}
+         WHERE u.canonical = ?1
+         ORDER BY u.id",
+        URL_RECORD_COLUMNS, URL_RECORD_JOINS
+    ))?;
+    let rows = stmt
+        .query_map(params![canonical], url_record_from_row)?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+    Ok(rows)
+}
+
+pub(super) fn duplicates_of(conn: &Connection, url: &str) -> Result<Vec<UrlRecord>> {
+    match crate::urlnorm::canonical(url) {
+        Some(canonical) => by_canonical(conn, &canonical),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub(super) fn find(conn: &Connection) -> Result<Vec<DuplicateGroup>> {
+    let keys = conn
+        .prepare(
+            "SELECT canonical FROM urls WHERE canonical IS NOT NULL
+             GROUP BY canonical HAVING COUNT(*) > 1
+             ORDER BY MIN(id)",
+        )?
+        .query_map(params![], |r| r.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+    keys.into_iter()
+        .map(|canonical| {
+            let records = by_canonical(conn, &canonical)?;
+            Ok(DuplicateGroup { canonical, records })
+        })
+        .collect()
+}
+
+pub(super) fn merge(conn: &Connection, ids: &[i64]) -> Result<i64> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    let (&keep, rest) = ids
+        .split_first()
+        .ok_or_else(|| anyhow!("no urls to merge"))?;
+    let tx = conn.unchecked_transaction()?;
+    for &id in &ids {
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM urls WHERE id = ?1)",
+            params![id],
+            |r| r.get(0),
+        )?;
+        if !exists {
+            return Err(anyhow!("no url with id {}", id));
+        }
+    }
+    for &id in rest {
+        tx.execute(
+            "INSERT OR IGNORE INTO favorites (url_id, added_at)
+             SELECT ?1, added_at FROM favorites WHERE url_id = ?2",
+            params![keep, id],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO url_tags (url_id, tag_id)
+             SELECT ?1, tag_id FROM url_tags WHERE url_id = ?2",
+            params![keep, id],
+        )?;
+        tx.execute(
+            "UPDATE visits SET url_id = ?1 WHERE url_id = ?2",
+            params![keep, id],
+        )?;
+        tx.execute(
+            "UPDATE urls SET no_fetch = 1
+             WHERE id = ?1 AND EXISTS(SELECT 1 FROM urls WHERE id = ?2 AND no_fetch = 1)",
+            params![keep, id],
+        )?;
+        delete_url(&tx, id)?;
+    }
+    tx.commit()?;
+    Ok(keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use anyhow::Result;
+    use std::path::Path;
+
+    #[test]
+    fn test_duplicates_are_found_by_canonical_url() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        let first = db.insert_url("Docs", "https://docs.rs/serde", 1)?;
+        let second = db.insert_url("Docs again", "http://DOCS.rs/serde/?utm_source=x", 2)?;
+        db.insert_url("Other", "https://docs.rs/tokio", 3)?;
+        let moved = db.insert_url("Elsewhere", "https://example.com/", 4)?;
+
+        let found: Vec<i64> = db
+            .duplicates_of("https://docs.rs/serde#readme")?
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(found, vec![first, second]);
+        assert!(db.duplicates_of("https://docs.rs/rand")?.is_empty());
+
+        let groups = db.find_duplicates()?;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].canonical, "https://docs.rs/serde");
+        assert_eq!(groups[0].ids(), vec![first, second]);
+
+        // editing a URL re-keys it
+        db.update_url(moved, None, Some("https://docs.rs/tokio/"))?;
+        assert_eq!(db.find_duplicates()?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_keeps_oldest_and_unions_the_rest() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        let keep = db.insert_url("Keep", "https://example.com/a", 1)?;
+        let dup = db.insert_url("Dup", "https://example.com/a/", 2)?;
+        let other = db.insert_url("Other", "https://example.com/b", 3)?;
+        db.tag_url(keep, "Work")?;
+        db.tag_url(dup, "Work")?;
+        db.tag_url(dup, "Later")?;
+        db.add_favorite(dup)?;
+        db.record_visit(dup, 1_000)?;
+        db.set_no_fetch(dup, true)?;
+
+        assert_eq!(db.merge_urls(&[dup, keep])?, keep);
+
+        assert!(db.get_by_id(dup)?.is_none());
+        let rec = db.get_by_id(keep)?.unwrap();
+        assert_eq!(rec.label, "Keep");
+        assert!(rec.is_favorite);
+        assert!(rec.no_fetch);
+        let mut tags = rec.tags.clone();
+        tags.sort();
+        assert_eq!(tags, vec!["Later".to_string(), "Work".to_string()]);
+        // the visit moved: the kept row outranks the newer, unvisited one
+        assert_eq!(db.list_frecent_at(10, 2_000)?[0].id, keep);
+        assert!(db.find_duplicates()?.is_empty());
+        assert!(db.get_by_id(other)?.is_some());
+
+        assert!(db.merge_urls(&[keep, 999]).is_err());
+        assert!(db.get_by_id(keep)?.is_some());
+        Ok(())
+    }
+}
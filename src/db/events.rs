@@ -0,0 +1,205 @@
+//! Change notifications published by the DB actor.
+//!
+//! `db_thread` collects the events of each request while handling it and
+//! publishes them once the request succeeded (after its transaction, if any,
+//! committed). Subscribers get every event in order; a burst of writes (an
+//! import, a background refresh) arrives as a burst of events.
+
+use super::{DbHandle, DbRequest};
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::thread;
+
+/// What changed in the library.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbEvent {
+    UrlInserted {
+        id: i64,
+    },
+    /// Label, URL, tags, metadata opt-out or link health changed.
+    UrlUpdated {
+        id: i64,
+    },
+    UrlDeleted {
+        id: i64,
+    },
+    UrlVisited {
+        id: i64,
+    },
+    FavoriteChanged {
+        id: i64,
+        favorite: bool,
+    },
+    /// A tag was renamed or merged, touching every URL carrying it.
+    TagsChanged,
+    SiteMetaUpdated {
+        origin: String,
+    },
+    IconAdded {
+        site_id: i64,
+        icon_id: i64,
+    },
+}
+
+impl DbEvent {
+    /// The URL row the event is about, if it is about a single one.
+    pub fn url_id(&self) -> Option<i64> {
+        match self {
+            DbEvent::UrlInserted { id }
+            | DbEvent::UrlUpdated { id }
+            | DbEvent::UrlDeleted { id }
+            | DbEvent::UrlVisited { id }
+            | DbEvent::FavoriteChanged { id, .. } => Some(*id),
+            DbEvent::TagsChanged | DbEvent::SiteMetaUpdated { .. } | DbEvent::IconAdded { .. } => {
+                None
+            }
+        }
+    }
+}
+
+/// A stream of [`DbEvent`]s, from `DbHandle::subscribe`. Ends when the DB
+/// actor stops; dropping it unsubscribes.
+pub struct Subscription {
+    rx: Receiver<DbEvent>,
+}
+
+impl Subscription {
+    /// For blocking consumers (worker threads): `recv`, `try_iter`, `select!`.
+    pub fn receiver(&self) -> &Receiver<DbEvent> {
+        &self.rx
+    }
+
+    /// For async consumers (Dioxus futures): events are forwarded from a
+    /// helper thread, which exits once the stream is dropped.
+    pub fn into_stream(self) -> futures::channel::mpsc::UnboundedReceiver<DbEvent> {
+        let (tx, stream) = futures::channel::mpsc::unbounded();
+        thread::spawn(move || {
+            for ev in self.rx.iter() {
+                if tx.unbounded_send(ev).is_err() {
+                    break;
+                }
+            }
+        });
+        stream
+    }
+}
+
+impl DbHandle {
+    /// Subscribe to changes made through any handle from now on.
+    pub fn subscribe(&self) -> Result<Subscription> {
+        let (tx, rx) = unbounded();
+        self.tx
+            .send(DbRequest::Subscribe { tx })
+            .map_err(|e| anyhow!("Failed to send subscribe request: {}", e))?;
+        Ok(Subscription { rx })
+    }
+}
+
+/// Send `events` to every subscriber, forgetting the ones that went away.
+pub(super) fn publish(subscribers: &mut Vec<Sender<DbEvent>>, events: Vec<DbEvent>) {
+    for ev in events {
+        subscribers.retain(|s| s.send(ev.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+    use std::path::Path;
+    use std::time::Duration;
+
+    /// Events published so far; a read round-trip first, since events follow
+    /// the response of the request that caused them.
+    fn drain(db: &DbHandle, sub: &Subscription) -> Result<Vec<DbEvent>> {
+        db.list_recent(1)?;
+        Ok(sub.receiver().try_iter().collect())
+    }
+
+    #[test]
+    fn test_mutations_publish_typed_events() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        let sub = db.subscribe()?;
+
+        let id = db.insert_url("One", "https://one.example.com/", 1)?;
+        db.update_url(id, Some("Uno"), None)?;
+        db.add_favorite(id)?;
+        db.remove_favorite(id)?;
+        db.tag_url(id, "Work")?;
+        db.rename_tag("Work", "Job")?;
+        db.record_visit(id, 5)?;
+        let origin = "https://one.example.com:443";
+        db.upsert_site_meta(origin, Some("One"), None, None, None)?;
+        let site_id = db.get_site_meta_by_origin(origin)?.unwrap().id;
+        let icon_id = db.insert_icon(
+            site_id,
+            "https://one.example.com/i.ico",
+            None,
+            None,
+            None,
+            vec![1],
+            None,
+        )?;
+        db.delete(id)?;
+
+        assert_eq!(
+            drain(&db, &sub)?,
+            vec![
+                DbEvent::UrlInserted { id },
+                DbEvent::UrlUpdated { id },
+                DbEvent::FavoriteChanged { id, favorite: true },
+                DbEvent::FavoriteChanged {
+                    id,
+                    favorite: false
+                },
+                DbEvent::UrlUpdated { id },
+                DbEvent::TagsChanged,
+                DbEvent::UrlVisited { id },
+                DbEvent::SiteMetaUpdated {
+                    origin: origin.to_string()
+                },
+                DbEvent::IconAdded { site_id, icon_id },
+                DbEvent::UrlDeleted { id },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reads_and_failures_publish_nothing() -> Result<()> {
+        let db = init_db(Path::new(":memory:"))?;
+        let id = db.insert_url("One", "https://one.example.com/", 1)?;
+        let sub = db.subscribe()?;
+
+        db.get_by_id(id)?;
+        db.list_favorites()?;
+        assert!(db.add_favorite(id + 1000).is_err());
+        assert!(db.update_url(id + 1000, Some("x"), None).is_err());
+        assert!(drain(&db, &sub)?.is_empty());
+
+        // a dropped subscriber is forgotten, the others keep receiving
+        let other = db.subscribe()?;
+        drop(sub);
+        db.delete(id)?;
+        assert_eq!(drain(&db, &other)?, vec![DbEvent::UrlDeleted { id }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_delivers_to_async_consumers() -> Result<()> {
+        use futures::StreamExt;
+        let db = init_db(Path::new(":memory:"))?;
+        let mut stream = db.subscribe()?.into_stream();
+        let id = db.insert_url("One", "https://one.example.com/", 1)?;
+
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        thread::spawn(move || {
+            let _ = tx.send(futures::executor::block_on(stream.next()));
+        });
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5))?,
+            Some(DbEvent::UrlInserted { id })
+        );
+        Ok(())
+    }
+}
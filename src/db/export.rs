@@ -0,0 +1,522 @@
+//! Library export to JSON, CSV and Netscape bookmark HTML.
+//!
+//! # JSON schema (`"format": "rustine-export"`, `"version": 1`)
+//!
+//! ```text
+//! {
+//!   "format": "rustine-export",
+//!   "version": 1,
+//!   "exported_at": <unix seconds>,
+//!   "urls": [{
+//!     "id": <int>, "label": <string>, "url": <string>, "added_at": <unix seconds>,
+//!     "origin": <string|null>, "favorite": <bool>, "favorited_at": <unix seconds|null>,
+//!     "tags": [<string>, ...]
+//!   }, ...],                                  // ordered by id
+//!   "sites": [{
+//!     "origin": <string>, "site_name": <string|null>, "description": <string|null>,
+//!     "manifest_url": <string|null>, "metadata_fetched_at": <unix seconds|null>,
+//!     "title", "og_site_name", "og_title", "og_description", "og_image",
+//!     "twitter_title", "twitter_description", "twitter_image",
+//!     "application_name", "theme_color": <string|null>,
+//!     "icons": [{                             // empty unless icons were requested
+//!       "src_url": <string>, "width": <int|null>, "height": <int|null>,
+//!       "mime": <string|null>, "fetched_at": <unix seconds|null>, "data_base64": <string>
+//!     }, ...]
+//!   }, ...]                                   // ordered by origin
+//! }
+//! ```
+//!
+//! Fields are only ever added to a given `version`; renames or removals bump it.
+
+use super::{
+    best_icon_order, page_details_from_row, DbHandle, DbRequest, PageDetails, PAGE_DETAILS_COLUMNS,
+};
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crossbeam_channel::unbounded;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+pub const EXPORT_FORMAT: &str = "rustine-export";
+pub const EXPORT_VERSION: i64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Full library as documented JSON, optionally embedding icons as base64.
+    Json { include_icons: bool },
+    /// One row per URL: `id,label,url,added_at,favorite,tags,site_name`.
+    Csv,
+    /// Netscape bookmark file importable by browsers.
+    NetscapeHtml,
+}
+
+impl ExportFormat {
+    /// Pick a format from a file extension (`json`, `csv`, `html`/`htm`).
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "json" => Some(ExportFormat::Json {
+                include_icons: true,
+            }),
+            "csv" => Some(ExportFormat::Csv),
+            "html" | "htm" => Some(ExportFormat::NetscapeHtml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Library {
+    pub format: &'static str,
+    pub version: i64,
+    pub exported_at: i64,
+    pub urls: Vec<ExportedUrl>,
+    pub sites: Vec<ExportedSite>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedUrl {
+    pub id: i64,
+    pub label: String,
+    pub url: String,
+    pub added_at: i64,
+    pub origin: Option<String>,
+    pub favorite: bool,
+    pub favorited_at: Option<i64>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedSite {
+    pub origin: String,
+    pub site_name: Option<String>,
+    pub description: Option<String>,
+    pub manifest_url: Option<String>,
+    pub metadata_fetched_at: Option<i64>,
+    #[serde(flatten)]
+    pub page: PageDetails,
+    pub icons: Vec<ExportedIcon>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedIcon {
+    pub src_url: String,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub mime: Option<String>,
+    pub fetched_at: Option<i64>,
+    pub data_base64: String,
+}
+
+impl DbHandle {
+    /// Write the whole library to `writer` in the requested format.
+    pub fn export<W: Write>(&self, format: ExportFormat, mut writer: W) -> Result<()> {
+        let include_icons = match format {
+            ExportFormat::Json { include_icons } => include_icons,
+            ExportFormat::Csv => false,
+            ExportFormat::NetscapeHtml => true,
+        };
+        let library = self.snapshot(include_icons)?;
+        match format {
+            ExportFormat::Json { .. } => {
+                serde_json::to_writer_pretty(&mut writer, &library)?;
+                writeln!(writer)?;
+            }
+            ExportFormat::Csv => write_csv(&library, &mut writer)?,
+            ExportFormat::NetscapeHtml => write_netscape(&library, &mut writer)?,
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn snapshot(&self, include_icons: bool) -> Result<Library> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::Snapshot {
+            include_icons,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send snapshot request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+}
+
+/// Read a consistent copy of the library. Runs on the DB thread.
+pub(super) fn snapshot(conn: &Connection, include_icons: bool) -> Result<Library> {
+    let tx = conn.unchecked_transaction()?;
+
+    let mut tags_by_url: HashMap<i64, Vec<String>> = HashMap::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT ut.url_id, t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+             ORDER BY t.name COLLATE NOCASE",
+        )?;
+        let rows = stmt.query_map(params![], |r| Ok((r.get::<_, i64>(0)?, r.get(1)?)))?;
+        for row in rows {
+            let (url_id, name) = row?;
+            tags_by_url.entry(url_id).or_default().push(name);
+        }
+    }
+
+    let urls = {
+        let mut stmt = tx.prepare(
+            "SELECT u.id, u.label, u.url, u.timestamp, u.origin, f.added_at
+             FROM urls u LEFT JOIN favorites f ON f.url_id = u.id
+             ORDER BY u.id",
+        )?;
+        let rows = stmt
+            .query_map(params![], |r| {
+                let favorited_at: Option<i64> = r.get(5)?;
+                Ok(ExportedUrl {
+                    id: r.get(0)?,
+                    label: r.get(1)?,
+                    url: r.get(2)?,
+                    added_at: r.get(3)?,
+                    origin: r.get(4)?,
+                    favorite: favorited_at.is_some(),
+                    favorited_at,
+                    tags: Vec::new(),
+                })
+            })?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        rows.into_iter()
+            .map(|mut u| {
+                u.tags = tags_by_url.remove(&u.id).unwrap_or_default();
+                u
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut icons_by_site: HashMap<i64, Vec<ExportedIcon>> = HashMap::new();
+    if include_icons {
+        // best 16px raster first, as `DbHandle::best_icon` ranks them: that is
+        // the one Netscape files get
+        let mut stmt = tx.prepare(concat!(
+            "SELECT site_id, src_url, width, height, mime, fetched_at, data FROM icons
+             ORDER BY site_id, CASE WHEN mime = 'image/svg+xml' THEN 1 ELSE 0 END, ",
+            best_icon_order!("16")
+        ))?;
+        let rows = stmt.query_map(params![], |r| {
+            Ok((
+                r.get::<_, i64>(0)?,
+                ExportedIcon {
+                    src_url: r.get(1)?,
+                    width: r.get(2)?,
+                    height: r.get(3)?,
+                    mime: r.get(4)?,
+                    fetched_at: r.get(5)?,
+                    data_base64: STANDARD.encode(r.get::<_, Vec<u8>>(6)?),
+                },
+            ))
+        })?;
+        for row in rows {
+            let (site_id, icon) = row?;
+            icons_by_site.entry(site_id).or_default().push(icon);
+        }
+    }
+
+    let sites = {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT id, origin, site_name, description, manifest_url, metadata_fetched_at, {}
+             FROM site_meta ORDER BY origin",
+            PAGE_DETAILS_COLUMNS
+        ))?;
+        let rows = stmt
+            .query_map(params![], |r| {
+                Ok((
+                    r.get::<_, i64>(0)?,
+                    ExportedSite {
+                        origin: r.get(1)?,
+                        site_name: r.get(2)?,
+                        description: r.get(3)?,
+                        manifest_url: r.get(4)?,
+                        metadata_fetched_at: r.get(5)?,
+                        page: page_details_from_row(r, 6)?,
+                        icons: Vec::new(),
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        rows.into_iter()
+            .map(|(id, mut site)| {
+                site.icons = icons_by_site.remove(&id).unwrap_or_default();
+                site
+            })
+            .collect()
+    };
+
+    tx.commit()?;
+    Ok(Library {
+        format: EXPORT_FORMAT,
+        version: EXPORT_VERSION,
+        exported_at: chrono::Utc::now().timestamp(),
+        urls,
+        sites,
+    })
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) || value.trim() != value {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_csv<W: Write>(library: &Library, w: &mut W) -> Result<()> {
+    let names: HashMap<&str, &str> = library
+        .sites
+        .iter()
+        .filter_map(|s| Some((s.origin.as_str(), s.site_name.as_deref()?)))
+        .collect();
+    writeln!(w, "id,label,url,added_at,favorite,tags,site_name")?;
+    for u in &library.urls {
+        let site_name = u
+            .origin
+            .as_deref()
+            .and_then(|o| names.get(o).copied())
+            .unwrap_or("");
+        writeln!(
+            w,
+            "{},{},{},{},{},{},{}",
+            u.id,
+            csv_field(&u.label),
+            csv_field(&u.url),
+            u.added_at,
+            u.favorite,
+            csv_field(&u.tags.join(";")),
+            csv_field(site_name)
+        )?;
+    }
+    Ok(())
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Netscape bookmark file: URLs go into a folder named after their first tag
+/// (untagged ones stay at the root); all tags are also listed in `TAGS`.
+fn write_netscape<W: Write>(library: &Library, w: &mut W) -> Result<()> {
+    let icons: HashMap<&str, &ExportedIcon> = library
+        .sites
+        .iter()
+        .filter_map(|s| Some((s.origin.as_str(), s.icons.first()?)))
+        .collect();
+    let bookmark = |u: &ExportedUrl, indent: &str| -> String {
+        let mut attrs = format!(
+            "HREF=\"{}\" ADD_DATE=\"{}\"",
+            html_escape(&u.url),
+            u.added_at
+        );
+        if !u.tags.is_empty() {
+            attrs.push_str(&format!(" TAGS=\"{}\"", html_escape(&u.tags.join(","))));
+        }
+        if let Some(icon) = u.origin.as_deref().and_then(|o| icons.get(o)) {
+            attrs.push_str(&format!(
+                " ICON=\"data:{};base64,{}\"",
+                html_escape(icon.mime.as_deref().unwrap_or("image/png")),
+                icon.data_base64
+            ));
+        }
+        format!("{}<DT><A {}>{}</A>\n", indent, attrs, html_escape(&u.label))
+    };
+
+    writeln!(w, "<!DOCTYPE NETSCAPE-Bookmark-file-1>")?;
+    writeln!(w, "<!-- This is an automatically generated file.")?;
+    writeln!(w, "     It will be read and overwritten.")?;
+    writeln!(w, "     DO NOT EDIT! -->")?;
+    writeln!(
+        w,
+        "<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">"
+    )?;
+    writeln!(w, "<TITLE>Bookmarks</TITLE>")?;
+    writeln!(w, "<H1>Bookmarks</H1>")?;
+    writeln!(w, "<DL><p>")?;
+
+    let mut folders: Vec<(&str, Vec<&ExportedUrl>)> = Vec::new();
+    for u in &library.urls {
+        match u.tags.first() {
+            Some(tag) => match folders.iter_mut().find(|(name, _)| *name == tag.as_str()) {
+                Some((_, list)) => list.push(u),
+                None => folders.push((tag.as_str(), vec![u])),
+            },
+            None => write!(w, "{}", bookmark(u, "    "))?,
+        }
+    }
+    folders.sort_by_key(|(name, _)| name.to_lowercase());
+    for (name, list) in folders {
+        writeln!(w, "    <DT><H3>{}</H3>", html_escape(name))?;
+        writeln!(w, "    <DL><p>")?;
+        for u in list {
+            write!(w, "{}", bookmark(u, "        "))?;
+        }
+        writeln!(w, "    </DL><p>")?;
+    }
+    writeln!(w, "</DL><p>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+    use std::path::Path;
+
+    fn sample() -> Result<DbHandle> {
+        let db = init_db(Path::new(":memory:"))?;
+        let rust = db.insert_url("Rust, \"the\" language", "https://www.rust-lang.org/", 10)?;
+        let docs = db.insert_url("Docs <rs>", "https://docs.rs/", 20)?;
+        db.insert_url("Plain", "https://example.com/", 30)?;
+        db.add_favorite(rust)?;
+        db.tag_url(rust, "Lang")?;
+        db.tag_url(docs, "Dev")?;
+        db.tag_url(docs, "Lang")?;
+        db.upsert_site_meta(
+            "https://www.rust-lang.org:443",
+            Some("Rust"),
+            None,
+            None,
+            Some(5),
+        )?;
+        db.set_page_details(
+            "https://www.rust-lang.org:443",
+            &PageDetails {
+                theme_color: Some("#dea584".to_string()),
+                ..PageDetails::default()
+            },
+        )?;
+        let site = db
+            .get_site_meta_by_origin("https://www.rust-lang.org:443")?
+            .unwrap();
+        db.insert_icon(
+            site.id,
+            "https://www.rust-lang.org/favicon.ico",
+            Some(16),
+            Some(16),
+            Some("image/png"),
+            vec![1, 2, 3],
+            Some(6),
+        )?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_export_json_schema() -> Result<()> {
+        let db = sample()?;
+        let mut out = Vec::new();
+        db.export(
+            ExportFormat::Json {
+                include_icons: true,
+            },
+            &mut out,
+        )?;
+        let v: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(v["format"], "rustine-export");
+        assert_eq!(v["version"], 1);
+        let urls = v["urls"].as_array().unwrap();
+        assert_eq!(urls.len(), 3);
+        assert_eq!(urls[0]["url"], "https://www.rust-lang.org/");
+        assert_eq!(urls[0]["favorite"], true);
+        assert_eq!(urls[0]["added_at"], 10);
+        assert_eq!(urls[1]["tags"], serde_json::json!(["Dev", "Lang"]));
+        assert_eq!(urls[2]["favorite"], false);
+        let site = &v["sites"][0];
+        assert_eq!(site["site_name"], "Rust");
+        assert_eq!(site["theme_color"], "#dea584");
+        assert!(site["og_title"].is_null());
+        assert_eq!(site["icons"][0]["data_base64"], "AQID");
+
+        let mut out = Vec::new();
+        db.export(
+            ExportFormat::Json {
+                include_icons: false,
+            },
+            &mut out,
+        )?;
+        let v: serde_json::Value = serde_json::from_slice(&out)?;
+        assert!(v["sites"][0]["icons"].as_array().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_csv_escapes_fields() -> Result<()> {
+        let db = sample()?;
+        let mut out = Vec::new();
+        db.export(ExportFormat::Csv, &mut out)?;
+        let text = String::from_utf8(out)?;
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "id,label,url,added_at,favorite,tags,site_name");
+        assert_eq!(
+            lines[1],
+            "1,\"Rust, \"\"the\"\" language\",https://www.rust-lang.org/,10,true,Lang,Rust"
+        );
+        assert_eq!(lines[2], "2,Docs <rs>,https://docs.rs/,20,false,Dev;Lang,");
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_netscape_round_trips_through_import_parser() -> Result<()> {
+        let db = sample()?;
+        // newer but worse for a favicon: the 16px PNG is still the one exported
+        let site = db
+            .get_site_meta_by_origin("https://www.rust-lang.org:443")?
+            .unwrap();
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>".to_vec();
+        let icon_url = "https://www.rust-lang.org/logo.svg";
+        db.insert_icon(
+            site.id,
+            icon_url,
+            None,
+            None,
+            Some("image/svg+xml"),
+            svg,
+            Some(9),
+        )?;
+        let icon_url = "https://www.rust-lang.org/apple-touch-icon.png";
+        db.insert_icon(
+            site.id,
+            icon_url,
+            Some(180),
+            Some(180),
+            Some("image/png"),
+            vec![4, 5, 6],
+            Some(9),
+        )?;
+        let mut out = Vec::new();
+        db.export(ExportFormat::NetscapeHtml, &mut out)?;
+        let html = String::from_utf8(out)?;
+        assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+
+        let marks = crate::bookmarks::parse_netscape(&html);
+        assert_eq!(marks.len(), 3);
+        let rust = marks
+            .iter()
+            .find(|m| m.url == "https://www.rust-lang.org/")
+            .unwrap();
+        assert_eq!(rust.title, "Rust, \"the\" language");
+        assert_eq!(rust.folders, vec!["Lang".to_string()]);
+        assert_eq!(rust.add_date, Some(10));
+        assert_eq!(
+            rust.icon.as_ref().map(|i| i.data.clone()),
+            Some(vec![1, 2, 3])
+        );
+        let docs = marks.iter().find(|m| m.url == "https://docs.rs/").unwrap();
+        assert_eq!(docs.title, "Docs <rs>");
+        assert_eq!(docs.folders, vec!["Dev".to_string()]);
+        let plain = marks
+            .iter()
+            .find(|m| m.url == "https://example.com/")
+            .unwrap();
+        assert!(plain.folders.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+//! Link-health history: one `link_checks` row per check of a saved URL.
+//!
+//! Checks are appended, never updated, so the details screen can show how a
+//! link behaved over time. The latest check of each URL drives the "broken"
+//! badge (`UrlRecord::link_broken`) and the redirect offer (`moved_to`).
+
+use super::{DbHandle, DbRequest};
+use anyhow::{anyhow, Result};
+use crossbeam_channel::unbounded;
+use rusqlite::{params, Connection};
+
+/// Statuses that prove the server is alive but won't serve us the page
+/// (login walls, rate limits); they don't count as broken.
+pub const REACHABLE_ERROR_STATUSES: [u16; 3] = [401, 403, 429];
+
+/// Outcome of checking one URL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkCheck {
+    pub url_id: i64,
+    pub checked_at: i64,
+    /// Final HTTP status; `None` when no response arrived.
+    pub status: Option<u16>,
+    /// URL after following redirects.
+    pub final_url: Option<String>,
+    pub latency_ms: i64,
+    /// `crate::http::FailureKind` name (`tls`, `timeout`, ...) when no response arrived.
+    pub error_kind: Option<String>,
+    pub error: Option<String>,
+}
+
+/// SQL twin of [`is_broken`] over the latest check joined as `lc`.
+pub(super) const LATEST_CHECK_BROKEN: &str =
+    "(lc.error IS NOT NULL OR (lc.status >= 400 AND lc.status NOT IN (401, 403, 429)))";
+
+/// A check failed outright, or the server answered with an error status
+/// other than `REACHABLE_ERROR_STATUSES`.
+pub fn is_broken(status: Option<u16>, failed: bool) -> bool {
+    failed || status.is_some_and(|s| s >= 400 && !REACHABLE_ERROR_STATUSES.contains(&s))
+}
+
+impl LinkCheck {
+    pub fn is_broken(&self) -> bool {
+        is_broken(self.status, self.error.is_some())
+    }
+}
+
+/// A saved URL whose last check is missing or too old.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DueLink {
+    pub url_id: i64,
+    pub url: String,
+}
+
+impl DbHandle {
+    /// Append a check to the URL's history.
+    pub fn record_link_check(&self, check: &LinkCheck) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::RecordLinkCheck {
+            check: check.clone(),
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send record_link_check request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// Most recent checks of a URL, newest first.
+    pub fn link_history(&self, url_id: i64, limit: i64) -> Result<Vec<LinkCheck>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::LinkHistory {
+            url_id,
+            limit,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send link_history request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+
+    /// URLs never checked or last checked more than `max_age_secs` before
+    /// `now`, oldest check first. URLs opted out of fetching are skipped.
+    pub fn links_due(&self, now: i64, max_age_secs: i64, limit: usize) -> Result<Vec<DueLink>> {
+        let (tx, rx) = unbounded();
+        let req = DbRequest::LinksDue {
+            now,
+            max_age_secs,
+            limit: limit as i64,
+            resp: tx,
+        };
+        self.tx
+            .send(req)
+            .map_err(|e| anyhow!("Failed to send links_due request: {}", e))?;
+        rx.recv()
+            .map_err(|e| anyhow!("DB response recv failed: {}", e))?
+    }
+}
+
+pub(super) fn record(conn: &Connection, check: &LinkCheck) -> Result<()> {
+    conn.execute(
+        "INSERT INTO link_checks (url_id, checked_at, status, final_url, latency_ms, error_kind, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            check.url_id,
+            check.checked_at,
+            check.status,
+            check.final_url,
+            check.latency_ms,
+            check.error_kind,
+            check.error
+        ],
+    )?;
+    Ok(())
+}
+
+pub(super) fn history(conn: &Connection, url_id: i64, limit: i64) -> Result<Vec<LinkCheck>> {
+    let mut stmt = conn.prepare(
+        "SELECT url_id, checked_at, status, final_url, latency_ms, error_kind, error
+         FROM link_checks WHERE url_id = ?1
+         ORDER BY checked_at DESC, id DESC LIMIT ?2",
+    )?;
+    let rows = stmt
+        .query_map(params![url_id, limit], |r| {
+            Ok(LinkCheck {
+                url_id: r.get(0)?,
+                checked_at: r.get(1)?,
+                status: r.get(2)?,
+                final_url: r.get(3)?,
+                latency_ms: r.get(4)?,
+                error_kind: r.get(5)?,
+                error: r.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+    Ok(rows)
+}
+
+pub(super) fn due(
+    conn: &Connection,
+    now: i64,
+    max_age_secs: i64,
+    limit: i64,
+) -> Result<Vec<DueLink>> {
+    let mut stmt = conn.prepare(
+        "SELECT u.id, u.url, MAX(lc.checked_at) AS last_checked
+         FROM urls u LEFT JOIN link_checks lc ON lc.url_id = u.id
+         WHERE u.no_fetch = 0
+         GROUP BY u.id
+         HAVING last_checked IS NULL OR last_checked <= ?1 - ?2
+         ORDER BY COALESCE(last_checked, 0), u.id
+         LIMIT ?3",
+    )?;
+    let rows = stmt
+        .query_map(params![now, max_age_secs, limit], |r| {
+            Ok(DueLink {
+                url_id: r.get(0)?,
+                url: r.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+    Ok(rows)
+}
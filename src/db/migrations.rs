@@ -0,0 +1,703 @@
+//! Embedded, ordered schema migrations for the DB actor.
+//!
+//! Each migration is applied exactly once, inside its own transaction, and
+//! recorded in `schema_migrations`. Append new steps to `MIGRATIONS`; never
+//! edit or reorder a step that has already shipped.
+//!
+//! Backfills never call into the rest of the crate: the logic each one needs
+//! is frozen in a module named after its version (`v2`, `v6`, …), so a given
+//! schema version always produces the same data. Fix bugs in the live code,
+//! and add a new migration if existing rows need them too.
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use rusqlite::{params, Connection};
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+    /// Optional Rust step run after `sql`, in the same transaction, for data
+    /// changes SQL alone cannot express.
+    pub backfill: Option<fn(&Connection) -> Result<()>>,
+}
+
+/// All known migrations, in ascending `version` order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        // `IF NOT EXISTS` lets databases created before the migration runner
+        // existed adopt version 1 without losing data.
+        sql: "CREATE TABLE IF NOT EXISTS urls (
+            id INTEGER PRIMARY KEY,
+            label TEXT NOT NULL,
+            url TEXT NOT NULL,
+            timestamp INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS favorites (
+            url_id INTEGER PRIMARY KEY,
+            added_at INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS site_meta (
+            id INTEGER PRIMARY KEY,
+            origin TEXT UNIQUE NOT NULL,
+            site_name TEXT,
+            description TEXT,
+            manifest_url TEXT,
+            metadata_fetched_at INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS icons (
+            id INTEGER PRIMARY KEY,
+            site_id INTEGER NOT NULL,
+            src_url TEXT NOT NULL,
+            width INTEGER,
+            height INTEGER,
+            mime TEXT,
+            data BLOB NOT NULL,
+            fetched_at INTEGER,
+            FOREIGN KEY(site_id) REFERENCES site_meta(id)
+        );",
+        backfill: None,
+    },
+    Migration {
+        version: 2,
+        name: "urls_origin",
+        sql: "ALTER TABLE urls ADD COLUMN origin TEXT;
+            CREATE INDEX IF NOT EXISTS idx_urls_origin ON urls(origin);",
+        backfill: Some(backfill_url_origins),
+    },
+    Migration {
+        version: 3,
+        name: "urls_fts",
+        // Full-text index over labels, URLs and site metadata, keyed by
+        // `urls.id`. Triggers keep it in sync with every write path.
+        sql: "CREATE VIRTUAL TABLE urls_fts USING fts5(
+                label, url, site_name, description,
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+            INSERT INTO urls_fts (rowid, label, url, site_name, description)
+                SELECT u.id, u.label, u.url, m.site_name, m.description
+                FROM urls u LEFT JOIN site_meta m ON m.origin = u.origin;
+
+            CREATE TRIGGER urls_fts_ai AFTER INSERT ON urls BEGIN
+                INSERT INTO urls_fts (rowid, label, url, site_name, description)
+                VALUES (
+                    new.id, new.label, new.url,
+                    (SELECT site_name FROM site_meta WHERE origin = new.origin),
+                    (SELECT description FROM site_meta WHERE origin = new.origin)
+                );
+            END;
+            CREATE TRIGGER urls_fts_au AFTER UPDATE ON urls BEGIN
+                DELETE FROM urls_fts WHERE rowid = old.id;
+                INSERT INTO urls_fts (rowid, label, url, site_name, description)
+                VALUES (
+                    new.id, new.label, new.url,
+                    (SELECT site_name FROM site_meta WHERE origin = new.origin),
+                    (SELECT description FROM site_meta WHERE origin = new.origin)
+                );
+            END;
+            CREATE TRIGGER urls_fts_ad AFTER DELETE ON urls BEGIN
+                DELETE FROM urls_fts WHERE rowid = old.id;
+            END;
+            CREATE TRIGGER urls_fts_meta_ai AFTER INSERT ON site_meta BEGIN
+                UPDATE urls_fts SET site_name = new.site_name, description = new.description
+                WHERE rowid IN (SELECT id FROM urls WHERE origin = new.origin);
+            END;
+            CREATE TRIGGER urls_fts_meta_au AFTER UPDATE ON site_meta BEGIN
+                UPDATE urls_fts SET site_name = NULL, description = NULL
+                WHERE rowid IN (SELECT id FROM urls WHERE origin = old.origin);
+                UPDATE urls_fts SET site_name = new.site_name, description = new.description
+                WHERE rowid IN (SELECT id FROM urls WHERE origin = new.origin);
+            END;
+            CREATE TRIGGER urls_fts_meta_ad AFTER DELETE ON site_meta BEGIN
+                UPDATE urls_fts SET site_name = NULL, description = NULL
+                WHERE rowid IN (SELECT id FROM urls WHERE origin = old.origin);
+            END;",
+        backfill: None,
+    },
+    Migration {
+        version: 4,
+        name: "tags",
+        sql: "CREATE TABLE tags (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE url_tags (
+                url_id INTEGER NOT NULL,
+                tag_id INTEGER NOT NULL,
+                PRIMARY KEY(url_id, tag_id),
+                FOREIGN KEY(url_id) REFERENCES urls(id),
+                FOREIGN KEY(tag_id) REFERENCES tags(id)
+            );
+            CREATE INDEX idx_url_tags_tag ON url_tags(tag_id);",
+        backfill: None,
+    },
+    Migration {
+        version: 5,
+        name: "visits",
+        sql: "CREATE TABLE visits (
+                id INTEGER PRIMARY KEY,
+                url_id INTEGER NOT NULL,
+                visited_at INTEGER NOT NULL,
+                FOREIGN KEY(url_id) REFERENCES urls(id)
+            );
+            CREATE INDEX idx_visits_url ON visits(url_id, visited_at);",
+        backfill: None,
+    },
+    Migration {
+        version: 6,
+        name: "icons_content_hash",
+        sql: "ALTER TABLE icons ADD COLUMN content_hash TEXT;",
+        backfill: Some(backfill_icon_hashes),
+    },
+    Migration {
+        version: 7,
+        name: "site_meta_page_details",
+        sql: "ALTER TABLE site_meta ADD COLUMN title TEXT;
+            ALTER TABLE site_meta ADD COLUMN og_site_name TEXT;
+            ALTER TABLE site_meta ADD COLUMN og_title TEXT;
+            ALTER TABLE site_meta ADD COLUMN og_description TEXT;
+            ALTER TABLE site_meta ADD COLUMN og_image TEXT;
+            ALTER TABLE site_meta ADD COLUMN twitter_title TEXT;
+            ALTER TABLE site_meta ADD COLUMN twitter_description TEXT;
+            ALTER TABLE site_meta ADD COLUMN twitter_image TEXT;
+            ALTER TABLE site_meta ADD COLUMN application_name TEXT;
+            ALTER TABLE site_meta ADD COLUMN theme_color TEXT;
+            ALTER TABLE site_meta ADD COLUMN preview_mime TEXT;
+            ALTER TABLE site_meta ADD COLUMN preview_data BLOB;",
+        backfill: None,
+    },
+    Migration {
+        version: 8,
+        name: "site_meta_refresh",
+        sql: "ALTER TABLE site_meta ADD COLUMN etag TEXT;
+            ALTER TABLE site_meta ADD COLUMN last_modified TEXT;
+            ALTER TABLE site_meta ADD COLUMN fetch_failures INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE site_meta ADD COLUMN next_fetch_at INTEGER;
+            ALTER TABLE site_meta ADD COLUMN last_fetch_error TEXT;",
+        backfill: None,
+    },
+    Migration {
+        version: 9,
+        name: "icons_svg_renditions",
+        sql: "",
+        backfill: Some(backfill_svg_renditions),
+    },
+    Migration {
+        version: 10,
+        name: "urls_no_fetch",
+        sql: "ALTER TABLE urls ADD COLUMN no_fetch INTEGER NOT NULL DEFAULT 0;",
+        backfill: None,
+    },
+    Migration {
+        version: 11,
+        name: "link_checks",
+        sql: "CREATE TABLE link_checks (
+                id INTEGER PRIMARY KEY,
+                url_id INTEGER NOT NULL,
+                checked_at INTEGER NOT NULL,
+                status INTEGER,
+                final_url TEXT,
+                latency_ms INTEGER NOT NULL,
+                error_kind TEXT,
+                error TEXT,
+                FOREIGN KEY(url_id) REFERENCES urls(id)
+            );
+            CREATE INDEX idx_link_checks_url ON link_checks(url_id, checked_at);",
+        backfill: None,
+    },
+    Migration {
+        version: 12,
+        name: "urls_canonical",
+        sql: "ALTER TABLE urls ADD COLUMN canonical TEXT;
+            CREATE INDEX idx_urls_canonical ON urls(canonical);",
+        backfill: Some(backfill_url_canonicals),
+    },
+];
+
+/// Populate `urls.origin` for rows inserted before the column existed.
+fn backfill_url_origins(conn: &Connection) -> Result<()> {
+    let rows = {
+        let mut stmt = conn.prepare("SELECT id, url FROM urls WHERE origin IS NULL")?;
+        let rows = stmt
+            .query_map(params![], |r| {
+                Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        rows
+    };
+    for (id, url) in rows {
+        conn.execute(
+            "UPDATE urls SET origin = ?1 WHERE id = ?2",
+            params![v2::origin(&url), id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Populate `urls.canonical` (see `v12::canonical`) for existing rows.
+fn backfill_url_canonicals(conn: &Connection) -> Result<()> {
+    let rows = {
+        let mut stmt = conn.prepare("SELECT id, url FROM urls")?;
+        let rows = stmt
+            .query_map(params![], |r| {
+                Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        rows
+    };
+    for (id, url) in rows {
+        conn.execute(
+            "UPDATE urls SET canonical = ?1 WHERE id = ?2",
+            params![v12::canonical(&url), id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Hash existing icons, fill in decodable dimensions, fold duplicate blobs of a
+/// site into their oldest row, then enforce uniqueness per site.
+fn backfill_icon_hashes(conn: &Connection) -> Result<()> {
+    let rows = {
+        let mut stmt = conn.prepare("SELECT id, data, width, height FROM icons")?;
+        let rows = stmt
+            .query_map(params![], |r| {
+                Ok((
+                    r.get::<_, i64>(0)?,
+                    r.get::<_, Vec<u8>>(1)?,
+                    r.get::<_, Option<i64>>(2)?,
+                    r.get::<_, Option<i64>>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        rows
+    };
+    for (id, data, width, height) in rows {
+        let (width, height) = match v6::icon_dimensions(&data) {
+            Some((w, h)) => (Some(w), Some(h)),
+            None => (width, height),
+        };
+        conn.execute(
+            "UPDATE icons SET content_hash = ?1, width = ?2, height = ?3 WHERE id = ?4",
+            params![v6::icon_hash(&data), width, height, id],
+        )?;
+    }
+    conn.execute_batch(
+        "UPDATE icons SET fetched_at = (
+                SELECT MAX(d.fetched_at) FROM icons d
+                WHERE d.site_id = icons.site_id AND d.content_hash = icons.content_hash
+            )
+            WHERE id IN (SELECT MIN(id) FROM icons GROUP BY site_id, content_hash);
+        DELETE FROM icons
+            WHERE id NOT IN (SELECT MIN(id) FROM icons GROUP BY site_id, content_hash);
+        CREATE UNIQUE INDEX idx_icons_site_hash ON icons(site_id, content_hash);",
+    )?;
+    Ok(())
+}
+
+/// Tag SVG icons stored before they were recognized and add the PNG
+/// renditions the window and tray needed at the time (see `v9`).
+fn backfill_svg_renditions(conn: &Connection) -> Result<()> {
+    let rows = {
+        let mut stmt = conn.prepare(
+            "SELECT id, site_id, src_url, mime, data, fetched_at FROM icons WHERE width IS NULL",
+        )?;
+        let rows = stmt
+            .query_map(params![], |r| {
+                Ok((
+                    r.get::<_, i64>(0)?,
+                    r.get::<_, i64>(1)?,
+                    r.get::<_, String>(2)?,
+                    r.get::<_, Option<String>>(3)?,
+                    r.get::<_, Vec<u8>>(4)?,
+                    r.get::<_, Option<i64>>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        rows
+    };
+    for (id, site_id, src_url, mime, data, fetched_at) in rows {
+        if !v9::is_svg(mime.as_deref(), &src_url, &data) {
+            continue;
+        }
+        conn.execute(
+            "UPDATE icons SET mime = ?1 WHERE id = ?2",
+            params![v9::SVG_MIME, id],
+        )?;
+        for px in v9::RASTER_SIZES {
+            // A broken SVG just keeps no renditions, as on a fresh fetch.
+            let Ok(png) = v9::rasterize(&data, px) else {
+                break;
+            };
+            let hash = v6::icon_hash(&png);
+            conn.execute(
+                "INSERT INTO icons (site_id, src_url, width, height, mime, data, fetched_at, content_hash)
+                 VALUES (?1, ?2, ?3, ?3, 'image/png', ?4, ?5, ?6)
+                 ON CONFLICT(site_id, content_hash) DO NOTHING",
+                params![site_id, src_url, px, png, fetched_at, hash],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// `crate::urlnorm::origin` as of migration 2.
+mod v2 {
+    use url::Url;
+
+    pub fn origin(url: &str) -> Option<String> {
+        let parsed = Url::parse(url.trim()).ok()?;
+        let host = parsed.host_str()?;
+        let port = parsed.port_or_known_default()?;
+        Some(format!(
+            "{}://{}:{}",
+            parsed.scheme(),
+            host.to_ascii_lowercase(),
+            port
+        ))
+    }
+}
+
+/// The icon hash and dimension helpers of `super` as of migration 6.
+mod v6 {
+    use sha2::{Digest, Sha256};
+
+    pub fn icon_hash(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    pub fn icon_dimensions(data: &[u8]) -> Option<(i64, i64)> {
+        let (w, h) = image::ImageReader::new(std::io::Cursor::new(data))
+            .with_guessed_format()
+            .ok()?
+            .into_dimensions()
+            .ok()?;
+        Some((w as i64, h as i64))
+    }
+}
+
+/// `crate::svg` as of migration 9.
+mod v9 {
+    use anyhow::{anyhow, Result};
+    use resvg::tiny_skia::{Pixmap, Transform};
+    use resvg::usvg::{Options, Tree};
+
+    pub const SVG_MIME: &str = "image/svg+xml";
+
+    pub const RASTER_SIZES: [u32; 4] = [16, 32, 48, 256];
+
+    pub fn is_svg(mime: Option<&str>, url: &str, data: &[u8]) -> bool {
+        if mime.is_some_and(|m| m.eq_ignore_ascii_case(SVG_MIME)) {
+            return true;
+        }
+        let path = url
+            .split(['?', '#'])
+            .next()
+            .unwrap_or(url)
+            .to_ascii_lowercase();
+        path.ends_with(".svg") || path.ends_with(".svgz") || sniff_svg(data)
+    }
+
+    fn sniff_svg(data: &[u8]) -> bool {
+        let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+        let head = head.trim_start_matches('\u{feff}').trim_start();
+        head.starts_with("<svg") || (head.starts_with("<?xml") && head.contains("<svg"))
+    }
+
+    pub fn rasterize(data: &[u8], px: u32) -> Result<Vec<u8>> {
+        let tree = Tree::from_data(data, &Options::default())
+            .map_err(|e| anyhow!("invalid SVG: {}", e))?;
+        let mut pixmap =
+            Pixmap::new(px, px).ok_or_else(|| anyhow!("invalid raster size {}px", px))?;
+        let size = tree.size();
+        let scale = px as f32 / size.width().max(size.height());
+        let transform = Transform::from_scale(scale, scale).post_translate(
+            (px as f32 - size.width() * scale) / 2.0,
+            (px as f32 - size.height() * scale) / 2.0,
+        );
+        resvg::render(&tree, transform, &mut pixmap.as_mut());
+        pixmap
+            .encode_png()
+            .map_err(|e| anyhow!("PNG encoding failed: {}", e))
+    }
+}
+
+/// `crate::urlnorm::canonical` as of migration 12.
+mod v12 {
+    use url::form_urlencoded;
+    use url::Url;
+
+    const TRACKING_PARAMS: [&str; 12] = [
+        "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "mc_cid", "mc_eid",
+        "igshid", "_ga", "_gl",
+    ];
+
+    pub fn canonical(url: &str) -> Option<String> {
+        let parsed = Url::parse(url.trim()).ok()?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return None;
+        }
+        let host = parsed.host_str()?.trim_end_matches('.');
+        let mut key = format!("https://{}", host.to_ascii_lowercase());
+        if let Some(port) = parsed.port() {
+            key.push_str(&format!(":{}", port));
+        }
+        key.push_str(parsed.path().trim_end_matches('/'));
+
+        let mut pairs: Vec<_> = parsed
+            .query_pairs()
+            .filter(|(name, _)| !is_tracking_param(name))
+            .collect();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        if !pairs.is_empty() {
+            key.push('?');
+            key.push_str(
+                &form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(pairs)
+                    .finish(),
+            );
+        }
+        if let Some(route) = parsed
+            .fragment()
+            .filter(|f| f.starts_with('/') || f.starts_with('!'))
+        {
+            key.push('#');
+            key.push_str(route);
+        }
+        Some(key)
+    }
+
+    fn is_tracking_param(name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+    }
+}
+
+/// Highest schema version this binary knows how to produce.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Schema version currently recorded in the database (0 if none).
+pub fn current_version(conn: &Connection) -> Result<i64> {
+    ensure_table(conn)?;
+    let v: Option<i64> = conn.query_row(
+        "SELECT MAX(version) FROM schema_migrations",
+        params![],
+        |r| r.get(0),
+    )?;
+    Ok(v.unwrap_or(0))
+}
+
+/// Bring the database up to `latest_version()`. Returns the number of steps applied.
+pub fn run(conn: &mut Connection) -> Result<usize> {
+    apply(conn, MIGRATIONS)
+}
+
+fn ensure_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        )",
+        params![],
+    )?;
+    Ok(())
+}
+
+fn apply(conn: &mut Connection, migrations: &[Migration]) -> Result<usize> {
+    let current = current_version(conn)?;
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+    if current > latest {
+        return Err(anyhow!(
+            "database schema version {} is newer than this build supports ({}); refusing to open it",
+            current,
+            latest
+        ));
+    }
+
+    let mut applied = 0;
+    for m in migrations.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(m.sql)
+            .map_err(|e| anyhow!("migration {} ({}) failed: {}", m.version, m.name, e))?;
+        if let Some(backfill) = m.backfill {
+            backfill(&tx)
+                .map_err(|e| anyhow!("migration {} ({}) failed: {}", m.version, m.name, e))?;
+        }
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![m.version, m.name, Utc::now().timestamp()],
+        )?;
+        tx.commit()?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Schema as created by `db_thread` before the migration runner existed.
+    const LEGACY_SCHEMA: &str = "
+        CREATE TABLE urls (
+            id INTEGER PRIMARY KEY,
+            label TEXT NOT NULL,
+            url TEXT NOT NULL,
+            timestamp INTEGER NOT NULL
+        );
+        CREATE TABLE favorites (
+            url_id INTEGER PRIMARY KEY,
+            added_at INTEGER NOT NULL
+        );
+        CREATE TABLE site_meta (
+            id INTEGER PRIMARY KEY,
+            origin TEXT UNIQUE NOT NULL,
+            site_name TEXT,
+            description TEXT,
+            manifest_url TEXT,
+            metadata_fetched_at INTEGER
+        );
+        CREATE TABLE icons (
+            id INTEGER PRIMARY KEY,
+            site_id INTEGER NOT NULL,
+            src_url TEXT NOT NULL,
+            width INTEGER,
+            height INTEGER,
+            mime TEXT,
+            data BLOB NOT NULL,
+            fetched_at INTEGER,
+            FOREIGN KEY(site_id) REFERENCES site_meta(id)
+        );
+        INSERT INTO urls (id, label, url, timestamp) VALUES (1, 'Example', 'https://example.com/', 100);
+        INSERT INTO urls (id, label, url, timestamp) VALUES (2, 'Example again', 'http://EXAMPLE.com/?utm_source=feed', 150);
+        INSERT INTO favorites (url_id, added_at) VALUES (1, 200);
+        INSERT INTO site_meta (id, origin, site_name) VALUES (1, 'https://example.com:443', 'Example Site');
+        INSERT INTO icons (site_id, src_url, mime, data, fetched_at) VALUES (1, 'https://example.com/favicon.ico', 'image/x-icon', x'010203', 300);
+        INSERT INTO icons (site_id, src_url, mime, data, fetched_at) VALUES (1, 'https://example.com/favicon.ico', 'image/x-icon', x'010203', 400);
+        INSERT INTO icons (site_id, src_url, mime, data, fetched_at) VALUES (1, 'https://example.com/icon.svg', 'text/plain', CAST('<svg xmlns=''http://www.w3.org/2000/svg'' width=''4'' height=''4''/>' AS BLOB), 500);
+    ";
+
+    #[test]
+    fn test_fresh_db_migrates_to_head() -> Result<()> {
+        let mut conn = Connection::open_in_memory()?;
+        assert_eq!(run(&mut conn)?, MIGRATIONS.len());
+        assert_eq!(current_version(&conn)?, latest_version());
+        // second run is a no-op
+        assert_eq!(run(&mut conn)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_db_upgrades_and_keeps_data() -> Result<()> {
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch(LEGACY_SCHEMA)?;
+        assert_eq!(current_version(&conn)?, 0);
+
+        run(&mut conn)?;
+        assert_eq!(current_version(&conn)?, latest_version());
+
+        let label: String =
+            conn.query_row("SELECT label FROM urls WHERE id = 1", params![], |r| {
+                r.get(0)
+            })?;
+        assert_eq!(label, "Example");
+        let favs: i64 =
+            conn.query_row("SELECT COUNT(*) FROM favorites", params![], |r| r.get(0))?;
+        assert_eq!(favs, 1);
+        // v6: duplicate blobs are folded into one row that keeps the latest fetch time
+        let (icon, fetched_at): (Vec<u8>, i64) = conn.query_row(
+            "SELECT data, fetched_at FROM icons WHERE src_url LIKE '%.ico'",
+            params![],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        assert_eq!(icon, vec![1u8, 2, 3]);
+        assert_eq!(fetched_at, 400);
+        // v9: the SVG is recognized and gains PNG renditions
+        let svg_mime: String = conn.query_row(
+            "SELECT mime FROM icons WHERE src_url LIKE '%.svg' AND width IS NULL",
+            params![],
+            |r| r.get(0),
+        )?;
+        assert_eq!(svg_mime, crate::svg::SVG_MIME);
+        let sizes = conn
+            .prepare("SELECT width FROM icons WHERE mime = 'image/png' ORDER BY width")?
+            .query_map(params![], |r| r.get::<_, u32>(0))?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        assert_eq!(sizes, crate::svg::RASTER_SIZES);
+
+        // v2: origins are backfilled and match what the webview stores in site_meta
+        let site_name: Option<String> = conn.query_row(
+            "SELECT m.site_name FROM urls u LEFT JOIN site_meta m ON m.origin = u.origin WHERE u.id = 1",
+            params![],
+            |r| r.get(0),
+        )?;
+        assert_eq!(site_name.as_deref(), Some("Example Site"));
+
+        // v3: existing rows are indexed for full-text search, including site metadata
+        let hits: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM urls_fts WHERE urls_fts MATCH 'site'",
+            params![],
+            |r| r.get(0),
+        )?;
+        assert_eq!(hits, 1);
+
+        // v12: both spellings of example.com share a canonical key
+        let distinct: i64 = conn.query_row(
+            "SELECT COUNT(DISTINCT canonical) FROM urls WHERE canonical IS NOT NULL",
+            params![],
+            |r| r.get(0),
+        )?;
+        assert_eq!(distinct, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() -> Result<()> {
+        let mut conn = Connection::open_in_memory()?;
+        run(&mut conn)?;
+        conn.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, 'future', 0)",
+            params![latest_version() + 1],
+        )?;
+        let err = run(&mut conn).unwrap_err();
+        assert!(
+            err.to_string().contains("newer"),
+            "unexpected error: {}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_step_is_rolled_back() -> Result<()> {
+        let steps = [
+            Migration {
+                version: 1,
+                name: "ok",
+                sql: "CREATE TABLE a (id INTEGER PRIMARY KEY);",
+                backfill: None,
+            },
+            Migration {
+                version: 2,
+                name: "broken",
+                sql: "CREATE TABLE b (id INTEGER PRIMARY KEY); SELECT * FROM missing_table;",
+                backfill: None,
+            },
+        ];
+        let mut conn = Connection::open_in_memory()?;
+        assert!(apply(&mut conn, &steps).is_err());
+        assert_eq!(current_version(&conn)?, 1);
+        let b_exists: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'b'",
+            params![],
+            |r| r.get(0),
+        )?;
+        assert_eq!(b_exists, 0);
+        Ok(())
+    }
+}
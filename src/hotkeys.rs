@@ -0,0 +1,252 @@
+//! Global keyboard shortcuts. Presses are delivered like tray clicks, as
+//! `TrayEvent`s on the tray channel, so the UI handles both the same way.
+
+use crate::config::HotkeyConfig;
+use crate::tray::TrayEvent;
+use anyhow::{anyhow, Result};
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use std::cell::RefCell;
+use std::sync::RwLock;
+
+/// What a global shortcut does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Launcher,
+    AddClipboard,
+}
+
+impl Action {
+    pub const ALL: [Action; 2] = [Action::Launcher, Action::AddClipboard];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Launcher => "Open the launcher",
+            Action::AddClipboard => "Add the clipboard URL",
+        }
+    }
+
+    /// The shortcut `config` binds to this action.
+    pub fn binding(self, config: &HotkeyConfig) -> &str {
+        match self {
+            Action::Launcher => &config.launcher,
+            Action::AddClipboard => &config.add_clipboard,
+        }
+    }
+
+    pub fn binding_mut(self, config: &mut HotkeyConfig) -> &mut String {
+        match self {
+            Action::Launcher => &mut config.launcher,
+            Action::AddClipboard => &mut config.add_clipboard,
+        }
+    }
+
+    fn event(self) -> TrayEvent {
+        match self {
+            Action::Launcher => TrayEvent::Launcher,
+            Action::AddClipboard => TrayEvent::AddClipboard,
+        }
+    }
+}
+
+/// A shortcut that could not be bound, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub action: Action,
+    pub reason: String,
+}
+
+struct Registry {
+    manager: GlobalHotKeyManager,
+    bound: Vec<(Action, HotKey)>,
+    /// From the last `start` or `rebind`.
+    conflicts: Vec<Conflict>,
+}
+
+thread_local! {
+    // The manager is not `Send`: it stays on the main thread that created it.
+    static REGISTRY: RefCell<Option<Registry>> = const { RefCell::new(None) };
+}
+
+/// Hotkey id -> action, read by the event handler on the hotkey thread.
+static ACTIONS: RwLock<Vec<(u32, Action)>> = RwLock::new(Vec::new());
+
+/// Register the shortcuts of `config`. Must be called on the main thread
+/// before the UI launches: the first hotkey event handler set wins, and the
+/// desktop runtime would otherwise install its own. Shortcuts that could not
+/// be bound are returned, the others are active.
+pub fn start(config: &HotkeyConfig) -> Result<Vec<Conflict>> {
+    let manager =
+        GlobalHotKeyManager::new().map_err(|e| anyhow!("global shortcuts unavailable: {}", e))?;
+    GlobalHotKeyEvent::set_event_handler(Some(|ev: GlobalHotKeyEvent| {
+        if ev.state() != HotKeyState::Pressed {
+            return;
+        }
+        let action = ACTIONS
+            .read()
+            .ok()
+            .and_then(|actions| actions.iter().find(|(id, _)| *id == ev.id()).map(|a| a.1));
+        if let Some(action) = action {
+            let _ = crate::tray::send_event(action.event());
+        }
+    }));
+
+    let (hotkeys, mut conflicts) = parse(config);
+    let bound = register(&manager, hotkeys, &mut conflicts);
+    publish(&bound);
+    REGISTRY.with(|registry| {
+        *registry.borrow_mut() = Some(Registry {
+            manager,
+            bound,
+            conflicts: conflicts.clone(),
+        });
+    });
+    Ok(conflicts)
+}
+
+/// Replace the active shortcuts with those of `config`, all or nothing: on
+/// any conflict the previous shortcuts stay and the conflicts are returned.
+/// Must be called on the main thread.
+pub fn rebind(config: &HotkeyConfig) -> Result<Vec<Conflict>> {
+    let (hotkeys, conflicts) = parse(config);
+    if !conflicts.is_empty() {
+        return Ok(conflicts);
+    }
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let registry = registry
+            .as_mut()
+            .ok_or_else(|| anyhow!("global shortcuts not started"))?;
+        let old = std::mem::take(&mut registry.bound);
+        unregister(&registry.manager, &old);
+
+        let mut conflicts = Vec::new();
+        let bound = register(&registry.manager, hotkeys, &mut conflicts);
+        registry.bound = if conflicts.is_empty() {
+            bound
+        } else {
+            unregister(&registry.manager, &bound);
+            register(&registry.manager, old, &mut Vec::new())
+        };
+        publish(&registry.bound);
+        registry.conflicts = conflicts.clone();
+        Ok(conflicts)
+    })
+}
+
+/// Shortcuts left unbound by the last `start` or `rebind`.
+pub fn conflicts() -> Vec<Conflict> {
+    REGISTRY.with(|registry| {
+        registry
+            .borrow()
+            .as_ref()
+            .map(|r| r.conflicts.clone())
+            .unwrap_or_default()
+    })
+}
+
+/// Parsed shortcuts of `config`. Empty bindings are off; unparsable ones and
+/// repeats of an earlier action's shortcut are reported instead.
+fn parse(config: &HotkeyConfig) -> (Vec<(Action, HotKey)>, Vec<Conflict>) {
+    let mut hotkeys: Vec<(Action, HotKey)> = Vec::new();
+    let mut conflicts = Vec::new();
+    for action in Action::ALL {
+        let binding = action.binding(config).trim();
+        if binding.is_empty() {
+            continue;
+        }
+        let reason = match binding.parse::<HotKey>() {
+            Err(e) => format!("invalid shortcut “{}”: {}", binding, e),
+            Ok(hotkey) => match hotkeys.iter().find(|(_, h)| h.id() == hotkey.id()) {
+                Some((other, _)) => format!("already used for “{}”", other.label()),
+                None => {
+                    hotkeys.push((action, hotkey));
+                    continue;
+                }
+            },
+        };
+        conflicts.push(Conflict { action, reason });
+    }
+    (hotkeys, conflicts)
+}
+
+/// Register `hotkeys`, returning the ones that succeeded; the others go to
+/// `conflicts`.
+fn register(
+    manager: &GlobalHotKeyManager,
+    hotkeys: Vec<(Action, HotKey)>,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<(Action, HotKey)> {
+    let mut bound = Vec::new();
+    for (action, hotkey) in hotkeys {
+        match manager.register(hotkey) {
+            Ok(()) => bound.push((action, hotkey)),
+            Err(
+                global_hotkey::Error::AlreadyRegistered(_)
+                | global_hotkey::Error::FailedToRegister(_),
+            ) => conflicts.push(Conflict {
+                action,
+                reason: "taken by another application".to_string(),
+            }),
+            Err(e) => conflicts.push(Conflict {
+                action,
+                reason: e.to_string(),
+            }),
+        }
+    }
+    bound
+}
+
+fn unregister(manager: &GlobalHotKeyManager, hotkeys: &[(Action, HotKey)]) {
+    for (_, hotkey) in hotkeys {
+        if let Err(e) = manager.unregister(*hotkey) {
+            eprintln!("failed to unregister global shortcut: {}", e);
+        }
+    }
+}
+
+fn publish(bound: &[(Action, HotKey)]) {
+    if let Ok(mut actions) = ACTIONS.write() {
+        *actions = bound.iter().map(|(a, h)| (h.id(), *a)).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(launcher: &str, add_clipboard: &str) -> HotkeyConfig {
+        HotkeyConfig {
+            launcher: launcher.to_string(),
+            add_clipboard: add_clipboard.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_default_bindings_parse_without_conflicts() {
+        let (hotkeys, conflicts) = parse(&HotkeyConfig::default());
+        assert!(conflicts.is_empty());
+        let actions: Vec<Action> = hotkeys.iter().map(|(a, _)| *a).collect();
+        assert_eq!(actions, Action::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_conflicting_and_invalid_bindings_are_reported() {
+        // same keys, spelled differently
+        let (hotkeys, conflicts) = parse(&bindings("ctrl+shift+KeyK", "Shift+Control+K"));
+        assert_eq!(hotkeys.len(), 1);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].action, Action::AddClipboard);
+        assert!(conflicts[0].reason.contains("Open the launcher"));
+
+        let (hotkeys, conflicts) = parse(&bindings("Ctrl+Nope", ""));
+        assert!(hotkeys.is_empty());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].action, Action::Launcher);
+        assert!(conflicts[0].reason.starts_with("invalid shortcut"));
+
+        // empty bindings are just off
+        let (hotkeys, conflicts) = parse(&bindings(" ", ""));
+        assert!(hotkeys.is_empty() && conflicts.is_empty());
+    }
+}
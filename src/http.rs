@@ -0,0 +1,496 @@
+//! Shared HTTP client for metadata and icon fetches.
+//!
+//! Every request is bounded: connect and total timeouts, a redirect cap and a
+//! maximum body size all come from [`HttpConfig`], so a slow or hostile host
+//! can't pin a fetch thread or exhaust memory. Hosts on the blocklist of
+//! [`NetworkConfig`] are refused before connecting, including redirect targets.
+
+use crate::config::{self, HttpConfig, NetworkConfig};
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::Method;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Why a request got no HTTP response at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Tls,
+    Timeout,
+    Connect,
+    Redirect,
+    Other,
+}
+
+impl FailureKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureKind::Tls => "tls",
+            FailureKind::Timeout => "timeout",
+            FailureKind::Connect => "connect",
+            FailureKind::Redirect => "redirect",
+            FailureKind::Other => "other",
+        }
+    }
+
+    fn classify(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return FailureKind::Timeout;
+        }
+        if e.is_redirect() {
+            return FailureKind::Redirect;
+        }
+        // rustls errors only surface as messages somewhere down the chain.
+        let mut source: Option<&dyn std::error::Error> = Some(e);
+        while let Some(err) = source {
+            let msg = err.to_string().to_ascii_lowercase();
+            if msg.contains("certificate") || msg.contains("tls") || msg.contains("ssl") {
+                return FailureKind::Tls;
+            }
+            source = err.source();
+        }
+        if e.is_connect() {
+            FailureKind::Connect
+        } else {
+            FailureKind::Other
+        }
+    }
+}
+
+/// A request that failed before any response arrived. Reach it through
+/// `anyhow::Error::downcast_ref` to tell TLS problems from timeouts.
+#[derive(Debug, thiserror::Error)]
+#[error("{method} {url} failed: {message}")]
+pub struct RequestError {
+    pub method: &'static str,
+    pub url: String,
+    pub kind: FailureKind,
+    message: String,
+}
+
+impl RequestError {
+    fn new(method: &'static str, url: &str, e: reqwest::Error) -> Self {
+        RequestError {
+            method,
+            url: url.to_string(),
+            kind: FailureKind::classify(&e),
+            message: e.to_string(),
+        }
+    }
+}
+
+pub struct HttpClient {
+    client: reqwest::blocking::Client,
+    max_body_bytes: u64,
+    network: Arc<NetworkConfig>,
+}
+
+/// A fully read response. Non-2xx statuses are returned, not turned into errors.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// URL after following redirects.
+    pub final_url: String,
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// `Content-Type` without parameters, lowercased.
+    pub fn content_type(&self) -> Option<String> {
+        let value = self.headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        let mime = value.split(';').next()?.trim().to_ascii_lowercase();
+        (!mime.is_empty()).then_some(mime)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+impl HttpClient {
+    pub fn new(cfg: &HttpConfig) -> Result<Self> {
+        Self::with_network(cfg, &NetworkConfig::default())
+    }
+
+    /// A client that also refuses the hosts blocked by `network`.
+    pub fn with_network(cfg: &HttpConfig, network: &NetworkConfig) -> Result<Self> {
+        let network = Arc::new(network.clone());
+        let redirect_network = network.clone();
+        let max_redirects = cfg.max_redirects;
+        let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > max_redirects {
+                attempt.error("too many redirects")
+            } else if attempt
+                .url()
+                .host_str()
+                .is_some_and(|h| redirect_network.is_host_blocked(h))
+            {
+                attempt.error("redirect to a blocked host")
+            } else {
+                attempt.follow()
+            }
+        });
+        let mut builder = reqwest::blocking::Client::builder()
+            .connect_timeout(Duration::from_secs(cfg.connect_timeout_secs))
+            .timeout(Duration::from_secs(cfg.timeout_secs))
+            .user_agent(cfg.user_agent.clone())
+            .redirect(redirect);
+        if let Some(proxy) = cfg.proxy.as_deref().filter(|p| !p.trim().is_empty()) {
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy.trim())
+                    .map_err(|e| anyhow!("invalid proxy {}: {}", proxy, e))?,
+            );
+        }
+        Ok(HttpClient {
+            client: builder.build()?,
+            max_body_bytes: cfg.max_body_bytes,
+            network,
+        })
+    }
+
+    pub fn get(&self, url: &str) -> Result<Response> {
+        self.get_with_headers(url, HeaderMap::new())
+    }
+
+    pub fn get_with_headers(&self, url: &str, headers: HeaderMap) -> Result<Response> {
+        let resp = self.send(Method::GET, url, headers)?;
+        if resp.content_length().unwrap_or(0) > self.max_body_bytes {
+            bail!(
+                "GET {}: body of {} bytes exceeds the {} byte limit",
+                url,
+                resp.content_length().unwrap_or(0),
+                self.max_body_bytes
+            );
+        }
+        let final_url = resp.url().to_string();
+        let status = resp.status().as_u16();
+        let headers = resp.headers().clone();
+        // Read one byte past the limit to tell "exactly at" from "over".
+        let mut body = Vec::new();
+        resp.take(self.max_body_bytes + 1)
+            .read_to_end(&mut body)
+            .map_err(|e| anyhow!("GET {}: reading body failed: {}", url, e))?;
+        if body.len() as u64 > self.max_body_bytes {
+            bail!(
+                "GET {}: body exceeds the {} byte limit",
+                url,
+                self.max_body_bytes
+            );
+        }
+        Ok(Response {
+            final_url,
+            status,
+            headers,
+            body,
+        })
+    }
+
+    /// `HEAD` request; the response has an empty body.
+    pub fn head(&self, url: &str) -> Result<Response> {
+        self.send(Method::HEAD, url, HeaderMap::new())
+            .map(Self::without_body)
+    }
+
+    /// `GET` that stops after the headers, for servers that reject `HEAD`.
+    pub fn get_without_body(&self, url: &str) -> Result<Response> {
+        self.send(Method::GET, url, HeaderMap::new())
+            .map(Self::without_body)
+    }
+
+    fn send(
+        &self,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+    ) -> Result<reqwest::blocking::Response> {
+        let name = if method == Method::HEAD {
+            "HEAD"
+        } else {
+            "GET"
+        };
+        if self.is_blocked(url) {
+            bail!("{} {}: host is on the blocklist", name, url);
+        }
+        self.client
+            .request(method, url)
+            .headers(headers)
+            .send()
+            .map_err(|e| RequestError::new(name, url, e).into())
+    }
+
+    fn without_body(resp: reqwest::blocking::Response) -> Response {
+        Response {
+            final_url: resp.url().to_string(),
+            status: resp.status().as_u16(),
+            headers: resp.headers().clone(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Whether requests to `url` are refused (unparseable URLs included).
+    pub fn is_blocked(&self, url: &str) -> bool {
+        match reqwest::Url::parse(url) {
+            Ok(u) => u
+                .host_str()
+                .is_some_and(|h| self.network.is_host_blocked(h)),
+            Err(_) => true,
+        }
+    }
+}
+
+static SHARED: OnceCell<HttpClient> = OnceCell::new();
+
+/// The process-wide client, built from the active config on first use.
+pub fn shared() -> Result<&'static HttpClient> {
+    SHARED.get_or_try_init(|| {
+        let cfg = config::current();
+        HttpClient::with_network(&cfg.http, &cfg.network)
+    })
+}
+
+/// Minimal HTTP/1.1 server for fetch tests: each route is a raw response
+/// writer, so tests can stall, loop redirects or stream oversized bodies.
+#[cfg(test)]
+pub(crate) mod test_server {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+
+    pub type Handler = Arc<dyn Fn(&Request, &mut TcpStream) + Send + Sync>;
+
+    pub struct Request {
+        pub method: String,
+        pub path: String,
+        pub headers: HashMap<String, String>,
+    }
+
+    pub struct TestServer {
+        pub base: String,
+    }
+
+    impl TestServer {
+        pub fn url(&self, path: &str) -> String {
+            format!("{}{}", self.base, path)
+        }
+    }
+
+    /// Serve `routes` (path → handler) on an ephemeral port; unknown paths get 404.
+    pub fn serve(routes: Vec<(&str, Handler)>) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Arc<HashMap<String, Handler>> = Arc::new(
+            routes
+                .into_iter()
+                .map(|(p, h)| (p.to_string(), h))
+                .collect(),
+        );
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let routes = routes.clone();
+                thread::spawn(move || handle(stream, &routes));
+            }
+        });
+        TestServer { base }
+    }
+
+    fn handle(mut stream: TcpStream, routes: &HashMap<String, Handler>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() {
+            return;
+        }
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or("GET").to_string();
+        let path = parts.next().unwrap_or("/").to_string();
+        let mut headers = HashMap::new();
+        loop {
+            let mut h = String::new();
+            if reader.read_line(&mut h).is_err() || h.trim().is_empty() {
+                break;
+            }
+            if let Some((k, v)) = h.split_once(':') {
+                headers.insert(k.trim().to_ascii_lowercase(), v.trim().to_string());
+            }
+        }
+        let req = Request {
+            method,
+            path,
+            headers,
+        };
+        match routes.get(&req.path) {
+            Some(handler) => handler(&req, &mut stream),
+            None => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+        }
+    }
+
+    pub fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) {
+        respond_with(stream, status, &[("Content-Type", content_type)], body);
+    }
+
+    pub fn respond_with(
+        stream: &mut TcpStream,
+        status: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) {
+        let mut head = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+        for (k, v) in headers {
+            head.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        head.push_str("\r\n");
+        let _ = stream.write_all(head.as_bytes());
+        let _ = stream.write_all(body);
+    }
+
+    /// Route serving a fixed body.
+    pub fn fixed(
+        status: &'static str,
+        content_type: &'static str,
+        body: impl Into<Vec<u8>>,
+    ) -> Handler {
+        let body = body.into();
+        Arc::new(move |_, s| respond(s, status, content_type, &body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_server::{fixed, respond, respond_with, serve, Handler};
+    use super::*;
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn test_config() -> HttpConfig {
+        HttpConfig {
+            connect_timeout_secs: 1,
+            timeout_secs: 1,
+            max_body_bytes: 1024,
+            max_redirects: 3,
+            ..HttpConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_get_follows_redirects_and_sends_user_agent() -> Result<()> {
+        let echo_ua: Handler = Arc::new(|req, s| {
+            let ua = req.headers.get("user-agent").cloned().unwrap_or_default();
+            respond(s, "200 OK", "text/plain; charset=utf-8", ua.as_bytes())
+        });
+        let hop: Handler =
+            Arc::new(|_, s| respond_with(s, "302 Found", &[("Location", "/ua")], b""));
+        let server = serve(vec![("/ua", echo_ua), ("/hop", hop)]);
+
+        let client = HttpClient::new(&test_config())?;
+        let resp = client.get(&server.url("/hop"))?;
+        assert!(resp.is_success());
+        assert_eq!(resp.final_url, server.url("/ua"));
+        assert_eq!(resp.content_type().as_deref(), Some("text/plain"));
+        assert_eq!(resp.text(), HttpConfig::default().user_agent);
+
+        let missing = client.get(&server.url("/nope"))?;
+        assert_eq!(missing.status, 404);
+        assert!(!missing.is_success());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_times_out_on_stalled_server() -> Result<()> {
+        let stall: Handler = Arc::new(|_, s| {
+            let _ = s.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabc");
+            std::thread::sleep(Duration::from_secs(5));
+        });
+        let server = serve(vec![("/stall", stall)]);
+        let client = HttpClient::new(&test_config())?;
+        let start = Instant::now();
+        assert!(client.get(&server.url("/stall")).is_err());
+        assert!(start.elapsed() < Duration::from_secs(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_caps_redirect_loops() -> Result<()> {
+        let looping: Handler =
+            Arc::new(|_, s| respond_with(s, "302 Found", &[("Location", "/loop")], b""));
+        let server = serve(vec![("/loop", looping)]);
+        let client = HttpClient::new(&test_config())?;
+        let err = client.get(&server.url("/loop")).unwrap_err();
+        assert!(err.to_string().contains("redirect"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_rejects_oversized_bodies() -> Result<()> {
+        let declared = fixed("200 OK", "text/html", vec![b'x'; 4096]);
+        // No Content-Length: the limit must hold while streaming too.
+        let streamed: Handler = Arc::new(|_, s| {
+            let _ = s.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n");
+            for _ in 0..64 {
+                let _ = s.write_all(&[b'y'; 1024]);
+            }
+        });
+        let small = fixed("200 OK", "text/html", vec![b'z'; 1024]);
+        let server = serve(vec![
+            ("/declared", declared),
+            ("/streamed", streamed),
+            ("/small", small),
+        ]);
+        let client = HttpClient::new(&test_config())?;
+        assert!(client.get(&server.url("/declared")).is_err());
+        assert!(client.get(&server.url("/streamed")).is_err());
+        assert_eq!(client.get(&server.url("/small"))?.body.len(), 1024);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocked_hosts_are_never_contacted() -> Result<()> {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        let page: Handler = Arc::new(move |_, s| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            respond(s, "200 OK", "text/plain", b"hi")
+        });
+        let server = serve(vec![("/", page)]);
+        // reachable through a host name that is not blocked, redirecting to one that is
+        let port = server.base.rsplit(':').next().unwrap().to_string();
+        let hop: Handler = Arc::new(move |_, s| {
+            let to = format!("http://127.0.0.1:{}/", port);
+            respond_with(s, "302 Found", &[("Location", to.as_str())], b"")
+        });
+        let other = serve(vec![("/hop", hop)]);
+        let network = NetworkConfig {
+            blocked_hosts: vec!["127.0.0.1".to_string()],
+            ..NetworkConfig::default()
+        };
+        let client = HttpClient::with_network(&test_config(), &network)?;
+
+        assert!(client.get(&server.url("/")).is_err());
+        let via_localhost = other.url("/hop").replace("127.0.0.1", "localhost");
+        let err = client.get(&via_localhost).unwrap_err();
+        assert!(err.to_string().contains("redirect"), "{}", err);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_proxy_is_rejected() {
+        let cfg = HttpConfig {
+            proxy: Some("::not a url::".to_string()),
+            ..HttpConfig::default()
+        };
+        assert!(HttpClient::new(&cfg).is_err());
+    }
+}
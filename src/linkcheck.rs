@@ -0,0 +1,277 @@
+//! Dead-link checker: probes saved URLs and records each outcome in
+//! `link_checks` (see `crate::db::LinkCheck`).
+//!
+//! A probe is a `HEAD`, retried as a `GET` that skips the body when the server
+//! answers with an error (plenty reject `HEAD`). Redirects are followed so the
+//! final URL can be offered as the row's new address. Checks obey the same
+//! network policy as metadata fetches.
+
+use crate::config::{self, NetworkConfig};
+use crate::db::{DbHandle, LinkCheck};
+use crate::http::{self, FailureKind, HttpClient, RequestError};
+use crate::metadata::{self, FetchReason};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::time::Instant;
+
+/// URLs checked per batch before asking the DB for more.
+const BATCH_SIZE: usize = 32;
+
+/// Probe `url` once. Never fails: transport errors end up in the result.
+pub fn probe(client: &HttpClient, url_id: i64, url: &str, now: i64) -> LinkCheck {
+    let start = Instant::now();
+    let mut res = client.head(url);
+    if res.as_ref().is_ok_and(|r| r.status >= 400) {
+        res = client.get_without_body(url);
+    }
+    let latency_ms = start.elapsed().as_millis() as i64;
+    let mut check = LinkCheck {
+        url_id,
+        checked_at: now,
+        status: None,
+        final_url: None,
+        latency_ms,
+        error_kind: None,
+        error: None,
+    };
+    match res {
+        Ok(resp) => {
+            check.status = Some(resp.status);
+            check.final_url = Some(resp.final_url);
+        }
+        Err(e) => {
+            let kind = e
+                .downcast_ref::<RequestError>()
+                .map_or(FailureKind::Other, |r| r.kind);
+            check.error_kind = Some(kind.as_str().to_string());
+            check.error = Some(e.to_string());
+        }
+    }
+    check
+}
+
+/// Check one saved URL and record the outcome. `None` when the network
+/// policy, the host blocklist or the URL's opt-out ruled the check out.
+pub fn check_and_record(
+    db: &DbHandle,
+    url_id: i64,
+    url: &str,
+    reason: FetchReason,
+) -> Result<Option<LinkCheck>> {
+    let network = config::current().network;
+    check_and_record_with(http::shared()?, db, url_id, url, reason, &network)
+}
+
+pub fn check_and_record_with(
+    client: &HttpClient,
+    db: &DbHandle,
+    url_id: i64,
+    url: &str,
+    reason: FetchReason,
+    network: &NetworkConfig,
+) -> Result<Option<LinkCheck>> {
+    if !metadata::fetch_permitted(network, db, url, reason)? || client.is_blocked(url) {
+        return Ok(None);
+    }
+    let check = probe(client, url_id, url, chrono::Utc::now().timestamp());
+    db.record_link_check(&check)?;
+    Ok(Some(check))
+}
+
+/// Check every URL not checked within `max_age_secs` (`0`: all of them),
+/// calling `on_checked` with the running count after each one. Returns how
+/// many were checked.
+pub fn check_due(
+    db: &DbHandle,
+    max_age_secs: i64,
+    reason: FetchReason,
+    on_checked: impl FnMut(usize),
+) -> Result<usize> {
+    let network = config::current().network;
+    check_due_with(
+        http::shared()?,
+        db,
+        max_age_secs,
+        reason,
+        &network,
+        on_checked,
+    )
+}
+
+pub fn check_due_with(
+    client: &HttpClient,
+    db: &DbHandle,
+    max_age_secs: i64,
+    reason: FetchReason,
+    network: &NetworkConfig,
+    mut on_checked: impl FnMut(usize),
+) -> Result<usize> {
+    // URLs the policy skips stay due; don't ask about them again this pass.
+    let mut seen = HashSet::new();
+    let mut checked = 0;
+    loop {
+        let now = chrono::Utc::now().timestamp();
+        let due = db.links_due(now, max_age_secs, BATCH_SIZE)?;
+        let fresh: Vec<_> = due.iter().filter(|l| seen.insert(l.url_id)).collect();
+        if fresh.is_empty() {
+            break;
+        }
+        for link in fresh {
+            if check_and_record_with(client, db, link.url_id, &link.url, reason, network)?.is_some()
+            {
+                checked += 1;
+                on_checked(checked);
+            }
+        }
+        if due.len() < BATCH_SIZE {
+            break;
+        }
+    }
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FetchPolicy, HttpConfig};
+    use crate::http::test_server::{fixed, respond_with, serve, Handler};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn test_client() -> Result<HttpClient> {
+        HttpClient::new(&HttpConfig {
+            connect_timeout_secs: 1,
+            timeout_secs: 2,
+            ..HttpConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_probe_records_status_redirects_and_errors() -> Result<()> {
+        let moved: Handler =
+            Arc::new(|_, s| respond_with(s, "301 Moved Permanently", &[("Location", "/new")], b""));
+        // rejects HEAD, serves GET: must not count as broken
+        let get_only: Handler = Arc::new(|req, s| {
+            let status = if req.method == "HEAD" {
+                "405 Method Not Allowed"
+            } else {
+                "200 OK"
+            };
+            respond_with(s, status, &[], b"")
+        });
+        let server = serve(vec![
+            ("/new", fixed("200 OK", "text/html", "new home")),
+            ("/old", moved),
+            ("/get-only", get_only),
+        ]);
+        let client = test_client()?;
+
+        let check = probe(&client, 1, &server.url("/old"), 100);
+        assert_eq!(check.status, Some(200));
+        assert_eq!(check.final_url, Some(server.url("/new")));
+        assert!(!check.is_broken());
+
+        assert_eq!(
+            probe(&client, 1, &server.url("/get-only"), 100).status,
+            Some(200)
+        );
+
+        let gone = probe(&client, 1, &server.url("/gone"), 100);
+        assert_eq!(gone.status, Some(404));
+        assert!(gone.is_broken());
+
+        // nothing listens on port 9 of localhost
+        let refused = probe(&client, 1, "http://127.0.0.1:9/", 100);
+        assert_eq!(refused.status, None);
+        assert_eq!(refused.error_kind.as_deref(), Some("connect"));
+        assert!(refused.is_broken());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checks_feed_badges_history_and_broken_view() -> Result<()> {
+        let server = serve(vec![("/ok", fixed("200 OK", "text/html", "ok"))]);
+        let client = test_client()?;
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        let ok = db.insert_url("Ok", &server.url("/ok"), 1)?;
+        let gone = db.insert_url("Gone", &server.url("/gone"), 2)?;
+        let network = NetworkConfig::default();
+
+        let mut progress = Vec::new();
+        let checked = check_due_with(&client, &db, 0, FetchReason::Manual, &network, |n| {
+            progress.push(n)
+        })?;
+        assert_eq!(checked, 2);
+        assert_eq!(progress, vec![1, 2]);
+        // everything was just checked: nothing is due for a day
+        assert!(db
+            .links_due(chrono::Utc::now().timestamp(), 86_400, 10)?
+            .is_empty());
+
+        assert!(!db.get_by_id(ok)?.unwrap().link_broken);
+        assert!(db.get_by_id(gone)?.unwrap().link_broken);
+        let broken: Vec<i64> = db.list_broken_links()?.iter().map(|r| r.id).collect();
+        assert_eq!(broken, vec![gone]);
+
+        check_and_record_with(
+            &client,
+            &db,
+            gone,
+            &server.url("/gone"),
+            FetchReason::Manual,
+            &network,
+        )?;
+        let history = db.link_history(gone, 10)?;
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|c| c.status == Some(404)));
+
+        // the policy is honored: nothing recorded when checks are off
+        let never = NetworkConfig {
+            fetch_policy: FetchPolicy::Never,
+            ..NetworkConfig::default()
+        };
+        assert_eq!(
+            check_and_record_with(
+                &client,
+                &db,
+                ok,
+                &server.url("/ok"),
+                FetchReason::Manual,
+                &never
+            )?,
+            None
+        );
+        assert_eq!(db.link_history(ok, 10)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_redirect_is_offered_and_applied() -> Result<()> {
+        let moved: Handler =
+            Arc::new(|_, s| respond_with(s, "301 Moved Permanently", &[("Location", "/new")], b""));
+        let server = serve(vec![
+            ("/old", moved),
+            ("/new", fixed("200 OK", "text/html", "new home")),
+        ]);
+        let client = test_client()?;
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        let id = db.insert_url("Moved", &server.url("/old"), 1)?;
+
+        check_and_record_with(
+            &client,
+            &db,
+            id,
+            &server.url("/old"),
+            FetchReason::Manual,
+            &NetworkConfig::default(),
+        )?;
+        let rec = db.get_by_id(id)?.unwrap();
+        assert_eq!(rec.moved_to, Some(server.url("/new")));
+
+        db.update_url(id, None, rec.moved_to.as_deref())?;
+        let rec = db.get_by_id(id)?.unwrap();
+        assert_eq!(rec.url, server.url("/new"));
+        assert_eq!(rec.moved_to, None);
+        Ok(())
+    }
+}
@@ -1,22 +1,57 @@
+mod autostart;
+mod bookmarks;
+mod config;
 mod db;
+mod hotkeys;
+mod http;
+mod linkcheck;
+mod metadata;
+mod refresh;
+mod svg;
 mod tray;
 mod ui;
+mod urlnorm;
 mod webview;
 // note: webview module removed; using system browser instead
 
-use dioxus_desktop::launch::launch;
-
 fn main() {
     println!("Starting Rustine (prototype)");
+    // set by the start-at-login entry: tray only, no main window
+    let start_minimized = std::env::args().any(|arg| arg == autostart::MINIMIZED_FLAG);
+
+    // Load settings before anything fetches over the network
+    let config_path = config::resolve_config_path().expect("failed to resolve config path");
+    let app_config = config::AppConfig::load(&config_path).unwrap_or_else(|e| {
+        eprintln!("{}; using default settings", e);
+        config::AppConfig::default()
+    });
+    if let Err(e) = autostart::sync(app_config.window.start_on_login) {
+        eprintln!("failed to update start at login: {}", e);
+    }
+    config::set_global(app_config).expect("failed to set global config");
 
     // Initialize DB (actor) and keep the handle
-    let db_handle = db::init_db().expect("failed to init db");
+    let db_path = db::resolve_db_path().expect("failed to resolve db path");
+    println!("Using database at {}", db_path.display());
+    let db_handle = db::init_db(&db_path).expect("failed to init db");
 
     // Set global DB handle for UI/tray access and start tray
     crate::db::set_global(db_handle.clone()).expect("failed to set global db");
     tray::start_tray(db_handle.clone()).expect("failed to start tray");
+    refresh::start_worker(db_handle.clone());
+
+    // Global shortcuts, delivered through the tray channel; a shortcut that is
+    // taken stays unbound and is shown in the shortcut settings
+    match hotkeys::start(&config::current().hotkeys) {
+        Ok(conflicts) => {
+            for c in conflicts {
+                eprintln!("“{}” shortcut not bound: {}", c.action.label(), c.reason);
+            }
+        }
+        Err(e) => eprintln!("{}", e),
+    }
 
-    // Launch Dioxus app (UI in src/ui.rs)
-    // pass empty plugin and state vectors
-    launch(ui::app, Vec::new(), Vec::new());
+    // Launch Dioxus app (UI in src/ui.rs); it keeps running in the tray
+    // until "Quit"
+    ui::launch(start_minimized);
 }
@@ -0,0 +1,909 @@
+//! Site metadata discovery: name, description, page details (title, OpenGraph,
+//! Twitter card, theme color), web manifest and candidate icons.
+//!
+//! Independent of the webview so it can run from the add-URL flow, background
+//! workers and tests alike. All requests go through [`crate::http`].
+
+use crate::config::{self, FetchPolicy, NetworkConfig};
+use crate::db::{DbHandle, PageDetails};
+use crate::http::{self, HttpClient};
+use crate::svg;
+use anyhow::{anyhow, bail, Result};
+use reqwest::header::{
+    HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use scraper::{Html, Selector};
+use url::Url;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SiteMetadata {
+    /// Normalized origin (see `crate::urlnorm::origin`), the `site_meta` key.
+    pub origin: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub manifest_url: Option<String>,
+    pub page: PageDetails,
+    /// Icons worth trying, in preference order.
+    pub icons: Vec<IconCandidate>,
+}
+
+impl SiteMetadata {
+    /// URL of the page's preview image (`og:image`, else `twitter:image`).
+    pub fn preview_image_url(&self) -> Option<&str> {
+        self.page
+            .og_image
+            .as_deref()
+            .or(self.page.twitter_image.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IconSource {
+    /// `<link rel="icon">` and friends.
+    Link,
+    /// `<link rel="apple-touch-icon">`.
+    AppleTouch,
+    /// An entry of the manifest's `icons` array.
+    Manifest,
+    /// The conventional `/favicon.ico`.
+    Favicon,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconCandidate {
+    pub url: String,
+    pub source: IconSource,
+    /// Raw `sizes` attribute/field, e.g. `"32x32"` or `"any"`.
+    pub sizes: Option<String>,
+    /// Declared `type`, e.g. `"image/png"`.
+    pub mime: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedIcon {
+    pub url: String,
+    pub mime: Option<String>,
+    /// Size from the candidate's `sizes`, used when the blob can't be decoded.
+    pub declared_size: Option<(i64, i64)>,
+    pub data: Vec<u8>,
+}
+
+/// Fetch `url` and discover its site metadata using the shared HTTP client.
+pub fn fetch_site_metadata(url: &str) -> Result<SiteMetadata> {
+    fetch_site_metadata_with(http::shared()?, url)
+}
+
+pub fn fetch_site_metadata_with(client: &HttpClient, url: &str) -> Result<SiteMetadata> {
+    match discover(client, url, None)? {
+        PageFetch::Fetched { meta, .. } => Ok(*meta),
+        PageFetch::NotModified { .. } => bail!("GET {}: unexpected 304", url),
+    }
+}
+
+/// Validators of a previous response, sent back as `If-None-Match` and
+/// `If-Modified-Since` so unchanged pages answer 304.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string())
+        };
+        Validators {
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+        }
+    }
+
+    fn request_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let pairs = [
+            (IF_NONE_MATCH, &self.etag),
+            (IF_MODIFIED_SINCE, &self.last_modified),
+        ];
+        for (name, value) in pairs {
+            if let Some(v) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, v);
+            }
+        }
+        headers
+    }
+}
+
+enum PageFetch {
+    NotModified {
+        validators: Validators,
+    },
+    Fetched {
+        meta: Box<SiteMetadata>,
+        validators: Validators,
+    },
+}
+
+fn discover(client: &HttpClient, url: &str, previous: Option<&Validators>) -> Result<PageFetch> {
+    let origin = crate::urlnorm::origin(url).ok_or_else(|| anyhow!("no origin for {}", url))?;
+    let headers = previous.map(|v| v.request_headers()).unwrap_or_default();
+    let page = client.get_with_headers(url, headers)?;
+    if page.status == 304 && previous.is_some() {
+        // A 304 may omit validators it didn't change.
+        let mut validators = previous.cloned().unwrap_or_default();
+        let fresh = Validators::from_headers(&page.headers);
+        validators.etag = fresh.etag.or(validators.etag);
+        validators.last_modified = fresh.last_modified.or(validators.last_modified);
+        return Ok(PageFetch::NotModified { validators });
+    }
+    if !page.is_success() {
+        bail!("GET {} returned HTTP {}", url, page.status);
+    }
+    let validators = Validators::from_headers(&page.headers);
+    let page_url = Url::parse(&page.final_url)?;
+    let mut meta = parse_page(&page_url, &page.text());
+    meta.origin = origin;
+
+    if let Some(manifest_url) = meta.manifest_url.clone() {
+        match fetch_manifest(client, &manifest_url) {
+            Ok(manifest) => {
+                meta.name = manifest.name.or(meta.name);
+                meta.description = meta.description.or(manifest.description);
+                meta.page.theme_color = meta.page.theme_color.or(manifest.theme_color);
+                meta.icons.extend(manifest.icons);
+            }
+            Err(e) => eprintln!("manifest {} skipped: {}", manifest_url, e),
+        }
+    }
+
+    if let Ok(favicon) = page_url.join("/favicon.ico") {
+        let favicon = favicon.to_string();
+        if !meta.icons.iter().any(|c| c.url == favicon) {
+            meta.icons.push(IconCandidate {
+                url: favicon,
+                source: IconSource::Favicon,
+                sizes: None,
+                mime: None,
+            });
+        }
+    }
+    Ok(PageFetch::Fetched {
+        meta: Box::new(meta),
+        validators,
+    })
+}
+
+/// Extract what the HTML itself declares; `origin` is left empty.
+pub fn parse_page(page_url: &Url, html: &str) -> SiteMetadata {
+    let doc = Html::parse_document(html);
+    let base = Selector::parse("base[href]").unwrap();
+    let base_url = doc
+        .select(&base)
+        .next()
+        .and_then(|b| page_url.join(b.value().attr("href")?).ok())
+        .unwrap_or_else(|| page_url.clone());
+
+    let title = Selector::parse("title").unwrap();
+    let title = doc
+        .select(&title)
+        .next()
+        .map(|t| t.text().collect::<String>())
+        .and_then(non_empty);
+
+    // OpenGraph uses `property`, Twitter cards and plain tags use `name`; pages
+    // mix them up, so accept either. The first occurrence wins.
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let metas = Selector::parse("meta[content]").unwrap();
+    for m in doc.select(&metas) {
+        let key = m.value().attr("property").or(m.value().attr("name"));
+        if let (Some(key), Some(content)) = (key, m.value().attr("content").and_then(non_empty)) {
+            tags.push((key.trim().to_ascii_lowercase(), content));
+        }
+    }
+    let tag = |keys: &[&str]| {
+        keys.iter().find_map(|k| {
+            tags.iter()
+                .find(|(key, _)| key == k)
+                .map(|(_, v)| v.clone())
+        })
+    };
+    let image = |keys: &[&str]| {
+        tag(keys)
+            .and_then(|src| base_url.join(&src).ok())
+            .map(|u| u.to_string())
+    };
+    let page = PageDetails {
+        title,
+        og_site_name: tag(&["og:site_name"]),
+        og_title: tag(&["og:title"]),
+        og_description: tag(&["og:description"]),
+        og_image: image(&["og:image", "og:image:url", "og:image:secure_url"]),
+        twitter_title: tag(&["twitter:title"]),
+        twitter_description: tag(&["twitter:description"]),
+        twitter_image: image(&["twitter:image", "twitter:image:src"]),
+        application_name: tag(&["application-name"]),
+        theme_color: tag(&["theme-color"]),
+    };
+    let name = page
+        .og_site_name
+        .clone()
+        .or_else(|| page.application_name.clone())
+        .or_else(|| page.title.clone());
+    let description = tag(&["description"])
+        .or_else(|| page.og_description.clone())
+        .or_else(|| page.twitter_description.clone());
+
+    let mut manifest_url = None;
+    let mut icons = Vec::new();
+    let links = Selector::parse("link[rel][href]").unwrap();
+    for link in doc.select(&links) {
+        let rel = link.value().attr("rel").unwrap_or_default().to_lowercase();
+        let Some(href) = link
+            .value()
+            .attr("href")
+            .and_then(|h| base_url.join(h).ok())
+        else {
+            continue;
+        };
+        let rels: Vec<&str> = rel.split_ascii_whitespace().collect();
+        if rels.contains(&"manifest") {
+            manifest_url.get_or_insert_with(|| href.to_string());
+        } else if rels
+            .iter()
+            .any(|r| *r == "apple-touch-icon" || *r == "apple-touch-icon-precomposed")
+        {
+            icons.push(IconCandidate {
+                url: href.to_string(),
+                source: IconSource::AppleTouch,
+                sizes: link.value().attr("sizes").and_then(non_empty),
+                mime: link.value().attr("type").and_then(non_empty),
+            });
+        } else if rels.contains(&"icon") {
+            icons.push(IconCandidate {
+                url: href.to_string(),
+                source: IconSource::Link,
+                sizes: link.value().attr("sizes").and_then(non_empty),
+                mime: link.value().attr("type").and_then(non_empty),
+            });
+        }
+    }
+    // Apple touch icons are usually large PNGs: try plain icons first.
+    icons.sort_by_key(|c| c.source == IconSource::AppleTouch);
+
+    SiteMetadata {
+        origin: String::new(),
+        name,
+        description,
+        manifest_url,
+        page,
+        icons,
+    }
+}
+
+struct Manifest {
+    name: Option<String>,
+    description: Option<String>,
+    theme_color: Option<String>,
+    icons: Vec<IconCandidate>,
+}
+
+fn fetch_manifest(client: &HttpClient, manifest_url: &str) -> Result<Manifest> {
+    let resp = client.get(manifest_url)?;
+    if !resp.is_success() {
+        bail!("HTTP {}", resp.status);
+    }
+    let json: serde_json::Value = serde_json::from_slice(&resp.body)?;
+    // Manifest icon URLs are relative to the manifest, not the page.
+    let manifest_base = Url::parse(&resp.final_url)?;
+    let field =
+        |v: &serde_json::Value, key: &str| v.get(key).and_then(|v| v.as_str()).and_then(non_empty);
+    let icons = json
+        .get("icons")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|icon| {
+            let url = manifest_base.join(icon.get("src")?.as_str()?).ok()?;
+            Some(IconCandidate {
+                url: url.to_string(),
+                source: IconSource::Manifest,
+                sizes: field(icon, "sizes"),
+                mime: field(icon, "type"),
+            })
+        })
+        .collect();
+    Ok(Manifest {
+        name: field(&json, "name").or_else(|| field(&json, "short_name")),
+        description: field(&json, "description"),
+        theme_color: field(&json, "theme_color"),
+        icons,
+    })
+}
+
+/// At most this many candidates are downloaded per site.
+const MAX_ICON_DOWNLOADS: usize = 8;
+
+/// Download every candidate (up to `MAX_ICON_DOWNLOADS`) that answers with an
+/// image body; HTML error pages served with 200 are skipped.
+pub fn fetch_icons(client: &HttpClient, candidates: &[IconCandidate]) -> Vec<FetchedIcon> {
+    candidates
+        .iter()
+        .take(MAX_ICON_DOWNLOADS)
+        .filter_map(|c| match client.get(&c.url) {
+            Ok(resp) if resp.is_success() && !resp.body.is_empty() => {
+                let mime = resp.content_type().or_else(|| c.mime.clone());
+                if mime.as_deref().is_some_and(|m| m.starts_with("text/html")) {
+                    return None;
+                }
+                Some(FetchedIcon {
+                    url: c.url.clone(),
+                    mime,
+                    declared_size: c.sizes.as_deref().and_then(parse_sizes),
+                    data: resp.body,
+                })
+            }
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("icon fetch skipped: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Download an image, refusing anything not served as `image/*`.
+fn fetch_image(client: &HttpClient, url: &str) -> Result<(String, Vec<u8>)> {
+    let resp = client.get(url)?;
+    if !resp.is_success() {
+        bail!("GET {} returned HTTP {}", url, resp.status);
+    }
+    match resp.content_type() {
+        Some(mime) if mime.starts_with("image/") => Ok((mime, resp.body)),
+        other => bail!("GET {}: not an image ({:?})", url, other),
+    }
+}
+
+/// First `WxH` entry of a `sizes` value (`"any"` and garbage yield `None`).
+fn parse_sizes(sizes: &str) -> Option<(i64, i64)> {
+    sizes.split_ascii_whitespace().find_map(|s| {
+        let (w, h) = s
+            .to_ascii_lowercase()
+            .split_once('x')
+            .map(|(w, h)| (w.parse::<i64>(), h.parse::<i64>()))?;
+        Some((w.ok()?, h.ok()?))
+    })
+}
+
+/// What triggered a fetch; the [`FetchPolicy`] decides which may go ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchReason {
+    /// The URL was just added.
+    Added,
+    /// The URL was opened.
+    Opened,
+    /// The user asked for a refresh.
+    Manual,
+    /// The background refresh worker.
+    Background,
+}
+
+impl FetchReason {
+    pub fn allowed_by(self, policy: FetchPolicy) -> bool {
+        match policy {
+            FetchPolicy::Always => true,
+            FetchPolicy::OnAdd => matches!(self, FetchReason::Added | FetchReason::Manual),
+            FetchPolicy::Never => false,
+        }
+    }
+}
+
+/// Discover metadata for `url`, download its icons and persist everything,
+/// as far as the configured network policy allows. Returns the `site_meta`
+/// id (pick an icon with `DbHandle::best_icon`), or `None` when the policy,
+/// the host blocklist or the URL's own opt-out ruled the fetch out.
+pub fn fetch_and_store(db: &DbHandle, url: &str, reason: FetchReason) -> Result<Option<i64>> {
+    let network = config::current().network;
+    fetch_and_store_with(http::shared()?, db, url, reason, &network)
+}
+
+/// Whether `url` may be contacted for `reason`. Makes no request.
+pub(crate) fn fetch_permitted(
+    network: &NetworkConfig,
+    db: &DbHandle,
+    url: &str,
+    reason: FetchReason,
+) -> Result<bool> {
+    if !reason.allowed_by(network.fetch_policy) {
+        return Ok(false);
+    }
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string));
+    if host.is_some_and(|h| network.is_host_blocked(&h)) {
+        return Ok(false);
+    }
+    Ok(!db.is_fetch_disabled(url)?)
+}
+
+/// Fetch and store metadata for `url` if `network` permits it (see
+/// [`fetch_and_store`]). Opened URLs and background refreshes are fetched
+/// with the validators stored for the site, so an unchanged page only bumps
+/// `metadata_fetched_at`; added URLs and manual refreshes always fetch in full. Failures are recorded on the site so the refresh
+/// worker backs off (see `DbHandle::record_fetch_failure`).
+pub fn fetch_and_store_with(
+    client: &HttpClient,
+    db: &DbHandle,
+    url: &str,
+    reason: FetchReason,
+    network: &NetworkConfig,
+) -> Result<Option<i64>> {
+    if !fetch_permitted(network, db, url, reason)? {
+        return Ok(None);
+    }
+    let origin = crate::urlnorm::origin(url).ok_or_else(|| anyhow!("no origin for {}", url))?;
+    let now = chrono::Utc::now().timestamp();
+    let res = (|| -> Result<i64> {
+        let conditional = matches!(reason, FetchReason::Opened | FetchReason::Background);
+        let previous = if conditional {
+            db.get_site_meta_by_origin(&origin)?.map(|site| Validators {
+                etag: site.etag,
+                last_modified: site.last_modified,
+            })
+        } else {
+            None
+        };
+        match discover(client, url, previous.as_ref())? {
+            PageFetch::NotModified { validators } => record_success(db, &origin, now, &validators),
+            PageFetch::Fetched { meta, validators } => {
+                let site_id = store(client, db, &meta, now)?;
+                record_success(db, &origin, now, &validators)?;
+                Ok(site_id)
+            }
+        }
+    })();
+    if let Err(e) = &res {
+        if let Err(db_err) = db.record_fetch_failure(&origin, now, &e.to_string()) {
+            eprintln!("failed to record fetch failure for {}: {}", origin, db_err);
+        }
+    }
+    res.map(Some)
+}
+
+fn record_success(db: &DbHandle, origin: &str, now: i64, validators: &Validators) -> Result<i64> {
+    db.record_fetch_success(
+        origin,
+        now,
+        validators.etag.as_deref(),
+        validators.last_modified.as_deref(),
+    )?;
+    db.get_site_meta_by_origin(origin)?
+        .map(|site| site.id)
+        .ok_or_else(|| anyhow!("site_meta row missing for {}", origin))
+}
+
+fn store(client: &HttpClient, db: &DbHandle, meta: &SiteMetadata, now: i64) -> Result<i64> {
+    let icons = fetch_icons(client, &meta.icons);
+    db.upsert_site_meta(
+        &meta.origin,
+        meta.name.as_deref(),
+        meta.description.as_deref(),
+        meta.manifest_url.as_deref(),
+        Some(now),
+    )?;
+    db.set_page_details(&meta.origin, &meta.page)?;
+    if let Some(preview_url) = meta.preview_image_url() {
+        match fetch_image(client, preview_url) {
+            Ok((mime, data)) => db.set_preview_image(&meta.origin, Some(&mime), Some(data))?,
+            Err(e) => eprintln!("preview image skipped: {}", e),
+        }
+    }
+    let site = db
+        .get_site_meta_by_origin(&meta.origin)?
+        .ok_or_else(|| anyhow!("site_meta row missing for {}", meta.origin))?;
+    for icon in icons {
+        if svg::is_svg(icon.mime.as_deref(), &icon.url, &icon.data) {
+            store_svg_icon(db, site.id, &icon, now)?;
+            continue;
+        }
+        let (width, height) = icon.declared_size.unzip();
+        db.insert_icon(
+            site.id,
+            &icon.url,
+            width,
+            height,
+            icon.mime.as_deref(),
+            icon.data,
+            Some(now),
+        )?;
+    }
+    Ok(site.id)
+}
+
+/// Keep the SVG itself (the webview renders it) plus PNG renditions at
+/// `svg::RASTER_SIZES` for the window and tray, which can't decode SVG.
+fn store_svg_icon(db: &DbHandle, site_id: i64, icon: &FetchedIcon, now: i64) -> Result<()> {
+    db.insert_icon(
+        site_id,
+        &icon.url,
+        None,
+        None,
+        Some(svg::SVG_MIME),
+        icon.data.clone(),
+        Some(now),
+    )?;
+    for px in svg::RASTER_SIZES {
+        match svg::rasterize(&icon.data, px) {
+            Ok(png) => {
+                db.insert_icon(
+                    site_id,
+                    &icon.url,
+                    Some(px as i64),
+                    Some(px as i64),
+                    Some("image/png"),
+                    png,
+                    Some(now),
+                )?;
+            }
+            Err(e) => {
+                eprintln!("rasterizing {} failed: {}", icon.url, e);
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn non_empty(s: impl AsRef<str>) -> Option<String> {
+    let s = s.as_ref().trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HttpConfig;
+    use crate::http::test_server::{fixed, respond_with, serve, Handler, TestServer};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const PAGE: &str = r##"<!doctype html><html><head>
+        <title> Example App </title>
+        <meta name="Description" content="Does example things">
+        <link rel="apple-touch-icon" href="/touch.png" sizes="180x180">
+        <link rel="shortcut icon" href="icons/fav-32.png" sizes="32x32" type="image/png">
+        <link rel="manifest" href="/app/manifest.json">
+        <meta property="og:site_name" content="Example Site">
+        <meta property="og:image" content="/og.png">
+        <meta name="theme-color" content="#112233">
+    </head><body></body></html>"##;
+
+    const SOCIAL: &str = r#"<!doctype html><html><head>
+        <title>Post - Social</title>
+        <meta property="og:title" content="A post">
+        <meta property="og:description" content="What the post says">
+        <meta name="twitter:card" content="summary">
+        <meta name="twitter:title" content="A post (tw)">
+        <meta name="twitter:description" content="Tweet text">
+        <meta name="twitter:image" content="img/card.png">
+        <meta name="application-name" content="Social">
+    </head><body></body></html>"#;
+
+    const MANIFEST: &str = r##"{
+        "name": "Example",
+        "short_name": "Ex",
+        "theme_color": "#ffffff",
+        "icons": [{"src": "icon-192.png", "sizes": "192x192", "type": "image/png"}]
+    }"##;
+
+    fn png(size: u32) -> Vec<u8> {
+        let mut out = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(size, size)
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    /// Fetch with the default (permissive) network policy.
+    fn fetch(client: &HttpClient, db: &DbHandle, url: &str, reason: FetchReason) -> Result<i64> {
+        fetch_and_store_with(client, db, url, reason, &NetworkConfig::default())?
+            .ok_or_else(|| anyhow!("fetch of {} was not permitted", url))
+    }
+
+    fn fixture() -> TestServer {
+        serve(vec![
+            ("/", fixed("200 OK", "text/html; charset=utf-8", PAGE)),
+            ("/bare", fixed("200 OK", "text/html", "<title>Bare</title>")),
+            ("/posts/1", fixed("200 OK", "text/html", SOCIAL)),
+            ("/og.png", fixed("200 OK", "image/png", png(64))),
+            (
+                "/app/manifest.json",
+                fixed("200 OK", "application/manifest+json", MANIFEST),
+            ),
+            (
+                "/icons/fav-32.png",
+                fixed("404 Not Found", "text/plain", "gone"),
+            ),
+            ("/touch.png", fixed("200 OK", "image/png", png(180))),
+            ("/app/icon-192.png", fixed("200 OK", "image/png", png(192))),
+            ("/favicon.ico", fixed("200 OK", "image/x-icon", png(16))),
+        ])
+    }
+
+    #[test]
+    fn test_fetch_site_metadata_collects_page_and_manifest() -> Result<()> {
+        let server = fixture();
+        let client = HttpClient::new(&HttpConfig::default())?;
+        let meta = fetch_site_metadata_with(&client, &server.url("/"))?;
+
+        assert_eq!(meta.origin, server.base);
+        assert_eq!(meta.name.as_deref(), Some("Example"));
+        assert_eq!(meta.description.as_deref(), Some("Does example things"));
+        assert_eq!(meta.manifest_url, Some(server.url("/app/manifest.json")));
+        assert_eq!(meta.page.title.as_deref(), Some("Example App"));
+        assert_eq!(meta.page.og_site_name.as_deref(), Some("Example Site"));
+        // the page's theme color wins over the manifest's
+        assert_eq!(meta.page.theme_color.as_deref(), Some("#112233"));
+        assert_eq!(
+            meta.preview_image_url(),
+            Some(server.url("/og.png").as_str())
+        );
+        let icons: Vec<(&str, IconSource, Option<&str>)> = meta
+            .icons
+            .iter()
+            .map(|c| (c.url.as_str(), c.source.clone(), c.sizes.as_deref()))
+            .collect();
+        let (fav, touch, manifest, ico) = (
+            server.url("/icons/fav-32.png"),
+            server.url("/touch.png"),
+            server.url("/app/icon-192.png"),
+            server.url("/favicon.ico"),
+        );
+        assert_eq!(
+            icons,
+            vec![
+                (fav.as_str(), IconSource::Link, Some("32x32")),
+                (touch.as_str(), IconSource::AppleTouch, Some("180x180")),
+                (manifest.as_str(), IconSource::Manifest, Some("192x192")),
+                (ico.as_str(), IconSource::Favicon, None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_site_metadata_without_manifest_and_failures() -> Result<()> {
+        let server = fixture();
+        let client = HttpClient::new(&HttpConfig::default())?;
+        let meta = fetch_site_metadata_with(&client, &server.url("/bare"))?;
+        assert_eq!(meta.name.as_deref(), Some("Bare"));
+        assert_eq!(meta.page.title.as_deref(), Some("Bare"));
+        assert_eq!(meta.manifest_url, None);
+        assert_eq!(meta.icons.len(), 1);
+        assert_eq!(meta.icons[0].source, IconSource::Favicon);
+
+        assert!(fetch_site_metadata_with(&client, &server.url("/missing")).is_err());
+        assert!(fetch_site_metadata_with(&client, "not a url").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_and_store_persists_meta_and_all_icons() -> Result<()> {
+        let server = fixture();
+        let client = HttpClient::new(&HttpConfig::default())?;
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        let id = db.insert_url("Example", &server.url("/"), 1)?;
+
+        let site_id = fetch(&client, &db, &server.url("/"), FetchReason::Added)?;
+        // Stored twice: identical blobs are not duplicated.
+        assert_eq!(
+            fetch(&client, &db, &server.url("/"), FetchReason::Added)?,
+            site_id
+        );
+
+        let rec = db.get_by_id(id)?.unwrap();
+        assert_eq!(rec.site_name.as_deref(), Some("Example"));
+        assert_eq!(rec.icon_data, Some(png(16)));
+        let site = db.get_site_meta_by_origin(&server.base)?.unwrap();
+        assert_eq!(site.description.as_deref(), Some("Does example things"));
+        assert_eq!(site.page.title.as_deref(), Some("Example App"));
+        assert_eq!(site.page.theme_color.as_deref(), Some("#112233"));
+        assert_eq!(site.preview_mime.as_deref(), Some("image/png"));
+        assert_eq!(site.preview_data, Some(png(64)));
+
+        let best = |px| -> Result<String> {
+            Ok(db
+                .best_icon(site_id, px)?
+                .map(|i| i.src_url)
+                .unwrap_or_default())
+        };
+        assert_eq!(best(16)?, server.url("/favicon.ico"));
+        assert_eq!(best(100)?, server.url("/touch.png"));
+        assert_eq!(best(512)?, server.url("/app/icon-192.png"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_svg_icon_is_kept_with_png_renditions() -> Result<()> {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 8 8"><circle cx="4" cy="4" r="4"/></svg>"#;
+        let server = serve(vec![
+            (
+                "/",
+                fixed(
+                    "200 OK",
+                    "text/html",
+                    r#"<link rel="icon" href="/icon.svg">"#,
+                ),
+            ),
+            // served with a generic type: recognized by extension and content
+            (
+                "/icon.svg",
+                fixed("200 OK", "application/octet-stream", svg),
+            ),
+        ]);
+        let client = HttpClient::new(&HttpConfig::default())?;
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        let id = db.insert_url("Vector", &server.url("/"), 1)?;
+        let site_id = fetch(&client, &db, &server.url("/"), FetchReason::Added)?;
+
+        // the list shows the SVG itself
+        let rec = db.get_by_id(id)?.unwrap();
+        assert_eq!(rec.icon_mime.as_deref(), Some(svg::SVG_MIME));
+        assert_eq!(rec.icon_data.as_deref(), Some(svg.as_bytes()));
+        // window and tray get a decodable PNG of the requested size
+        for px in svg::RASTER_SIZES {
+            let icon = db.best_icon(site_id, px)?.unwrap();
+            assert_eq!(icon.mime.as_deref(), Some("image/png"));
+            let img = image::load_from_memory(&icon.data)?;
+            assert_eq!((img.width(), img.height()), (px, px));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_network_policy_prevents_requests() -> Result<()> {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let page: Handler = Arc::new(move |_, s| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            respond_with(
+                s,
+                "200 OK",
+                &[("Content-Type", "text/html")],
+                b"<title>T</title>",
+            )
+        });
+        let server = serve(vec![("/", page)]);
+        let client = HttpClient::new(&HttpConfig::default())?;
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        let url = server.url("/");
+        let id = db.insert_url("Local", &url, 1)?;
+        let policy = |fetch_policy| NetworkConfig {
+            fetch_policy,
+            ..NetworkConfig::default()
+        };
+
+        for reason in [
+            FetchReason::Added,
+            FetchReason::Opened,
+            FetchReason::Manual,
+            FetchReason::Background,
+        ] {
+            let never =
+                fetch_and_store_with(&client, &db, &url, reason, &policy(FetchPolicy::Never));
+            assert_eq!(never?, None);
+        }
+        for reason in [FetchReason::Opened, FetchReason::Background] {
+            let on_add =
+                fetch_and_store_with(&client, &db, &url, reason, &policy(FetchPolicy::OnAdd));
+            assert_eq!(on_add?, None);
+        }
+        let blocked = NetworkConfig {
+            blocked_hosts: vec!["127.0.0.1".to_string()],
+            ..NetworkConfig::default()
+        };
+        assert_eq!(
+            fetch_and_store_with(&client, &db, &url, FetchReason::Added, &blocked)?,
+            None
+        );
+        db.set_no_fetch(id, true)?;
+        assert!(db.get_by_id(id)?.unwrap().no_fetch);
+        assert_eq!(
+            fetch_and_store_with(
+                &client,
+                &db,
+                &url,
+                FetchReason::Manual,
+                &policy(FetchPolicy::Always)
+            )?,
+            None
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+        // nothing was recorded either: a skipped fetch is not a failure
+        assert!(db.get_site_meta_by_origin(&server.base)?.is_none());
+
+        db.set_no_fetch(id, false)?;
+        let on_add = fetch_and_store_with(
+            &client,
+            &db,
+            &url,
+            FetchReason::Added,
+            &policy(FetchPolicy::OnAdd),
+        );
+        assert!(on_add?.is_some());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_uses_validators_and_records_failures() -> Result<()> {
+        let full_responses = Arc::new(AtomicUsize::new(0));
+        let counter = full_responses.clone();
+        let page: Handler = Arc::new(move |req, s| {
+            if req.headers.get("if-none-match").map(String::as_str) == Some("\"v1\"") {
+                respond_with(s, "304 Not Modified", &[("ETag", "\"v1\"")], b"");
+            } else {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let headers = [("Content-Type", "text/html"), ("ETag", "\"v1\"")];
+                respond_with(s, "200 OK", &headers, b"<title>Versioned</title>");
+            }
+        });
+        let server = serve(vec![("/", page)]);
+        let client = HttpClient::new(&HttpConfig::default())?;
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        let url = server.url("/");
+
+        let site_id = fetch(&client, &db, &url, FetchReason::Manual)?;
+        let site = db.get_site_meta_by_origin(&server.base)?.unwrap();
+        assert_eq!(site.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(site.page.title.as_deref(), Some("Versioned"));
+
+        // unchanged page: 304, nothing re-downloaded
+        assert_eq!(fetch(&client, &db, &url, FetchReason::Background)?, site_id);
+        assert_eq!(fetch(&client, &db, &url, FetchReason::Opened)?, site_id);
+        assert_eq!(full_responses.load(Ordering::SeqCst), 1);
+        // a manual refresh ignores the validators
+        fetch(&client, &db, &url, FetchReason::Manual)?;
+        assert_eq!(full_responses.load(Ordering::SeqCst), 2);
+
+        assert!(fetch(&client, &db, &server.url("/gone"), FetchReason::Manual).is_err());
+        let site = db.get_site_meta_by_origin(&server.base)?.unwrap();
+        assert_eq!(site.fetch_failures, 1);
+        assert!(site.next_fetch_at.unwrap() > site.metadata_fetched_at.unwrap());
+        assert!(site.last_fetch_error.unwrap().contains("404"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_page_reads_opengraph_and_twitter_tags() {
+        let url = Url::parse("https://social.example/posts/1").unwrap();
+        let meta = parse_page(&url, SOCIAL);
+        assert_eq!(meta.name.as_deref(), Some("Social"));
+        assert_eq!(meta.description.as_deref(), Some("What the post says"));
+        let page = &meta.page;
+        assert_eq!(page.title.as_deref(), Some("Post - Social"));
+        assert_eq!(page.og_title.as_deref(), Some("A post"));
+        assert_eq!(page.twitter_title.as_deref(), Some("A post (tw)"));
+        assert_eq!(page.twitter_description.as_deref(), Some("Tweet text"));
+        assert_eq!(
+            page.twitter_image.as_deref(),
+            Some("https://social.example/posts/img/card.png")
+        );
+        assert_eq!(page.og_image, None);
+        assert_eq!(page.application_name.as_deref(), Some("Social"));
+        assert_eq!(
+            meta.preview_image_url(),
+            Some("https://social.example/posts/img/card.png")
+        );
+    }
+
+    #[test]
+    fn test_parse_sizes() {
+        assert_eq!(parse_sizes("32x32"), Some((32, 32)));
+        assert_eq!(parse_sizes("16X16 32x32"), Some((16, 16)));
+        assert_eq!(parse_sizes("any"), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Background worker that keeps site metadata and link health fresh.
+//!
+//! Every `check_interval_secs` (or right after [`wake`]) the worker asks the
+//! DB for origins whose metadata is older than `refresh_after_days` and not
+//! backing off, and refreshes them one by one with conditional requests. Then
+//! it re-checks saved URLs last checked over `recheck_after_days` ago. It
+//! runs on its own thread so the UI never waits on the network, and stays
+//! idle unless the network policy is `always`. What it stores reaches the UI
+//! and tray as DB change events.
+
+use crate::config;
+use crate::config::FetchPolicy;
+use crate::db::DbHandle;
+use crate::linkcheck;
+use crate::metadata::{self, FetchReason};
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
+use once_cell::sync::OnceCell;
+use std::collections::HashSet;
+use std::thread;
+use std::time::Duration;
+
+/// Sites refreshed per pass before the worker checks for new work again.
+const BATCH_SIZE: usize = 16;
+
+static WAKE_TX: OnceCell<Sender<()>> = OnceCell::new();
+
+/// Start the refresh worker. Calling it again is a no-op.
+pub fn start_worker(db: DbHandle) {
+    let (wake_tx, wake_rx) = unbounded();
+    if WAKE_TX.set(wake_tx).is_err() {
+        return;
+    }
+
+    thread::spawn(move || loop {
+        let app = config::current();
+        // Background fetches only happen under the "always" network policy.
+        if app.network.fetch_policy == FetchPolicy::Always {
+            let max_age_secs = (app.metadata.refresh_after_days as i64).saturating_mul(86_400);
+            refresh_stale(&db, max_age_secs);
+            if app.links.recheck_after_days > 0 {
+                let max_age_secs = (app.links.recheck_after_days as i64).saturating_mul(86_400);
+                if let Err(e) =
+                    linkcheck::check_due(&db, max_age_secs, FetchReason::Background, |_| {})
+                {
+                    eprintln!("scheduled link check failed: {}", e);
+                }
+            }
+        }
+        let interval = Duration::from_secs(app.metadata.check_interval_secs.max(1));
+        match wake_rx.recv_timeout(interval) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        // Collapse wake-ups queued while we were busy into this pass.
+        while wake_rx.try_recv().is_ok() {}
+    });
+}
+
+/// Refresh every stale origin once.
+fn refresh_stale(db: &DbHandle, max_age_secs: i64) {
+    // A row that can't be updated must not keep coming back and spin the loop.
+    let mut seen = HashSet::new();
+    loop {
+        let now = chrono::Utc::now().timestamp();
+        let stale = match db.stale_sites(now, max_age_secs, BATCH_SIZE) {
+            Ok(stale) => stale,
+            Err(e) => {
+                eprintln!("metadata refresh: listing stale sites failed: {}", e);
+                break;
+            }
+        };
+        let fresh: Vec<_> = stale
+            .iter()
+            .filter(|site| seen.insert(site.origin.clone()))
+            .collect();
+        if fresh.is_empty() {
+            break;
+        }
+        for site in &fresh {
+            // Failures are recorded on the row (and back off), so just log.
+            if let Err(e) = metadata::fetch_and_store(db, &site.url, FetchReason::Background) {
+                eprintln!("metadata refresh for {} failed: {}", site.origin, e);
+            }
+        }
+        if stale.len() < BATCH_SIZE {
+            break;
+        }
+    }
+}
+
+/// Ask the worker to look for stale sites now instead of at the next tick.
+pub fn wake() {
+    if let Some(tx) = WAKE_TX.get() {
+        let _ = tx.send(());
+    }
+}
@@ -0,0 +1,117 @@
+//! SVG icon support: detection and rasterization to PNG.
+//!
+//! The `image` crate can't decode SVG, so icons in that format are rendered
+//! with `resvg` to the fixed sizes the window and tray need. The original SVG
+//! is kept as well since the webview renders it directly.
+
+use anyhow::{anyhow, Result};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{Options, Tree};
+
+pub const SVG_MIME: &str = "image/svg+xml";
+
+/// PNG renditions stored next to every SVG icon.
+pub const RASTER_SIZES: [u32; 4] = [16, 32, 48, 256];
+
+/// Whether an icon is SVG, judged by its MIME type, URL extension or content.
+pub fn is_svg(mime: Option<&str>, url: &str, data: &[u8]) -> bool {
+    if mime.is_some_and(|m| m.eq_ignore_ascii_case(SVG_MIME)) {
+        return true;
+    }
+    let path = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(url)
+        .to_ascii_lowercase();
+    path.ends_with(".svg") || path.ends_with(".svgz") || sniff_svg(data)
+}
+
+/// Markup whose first element is `<svg`, after an optional XML prologue.
+fn sniff_svg(data: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    head.starts_with("<svg") || (head.starts_with("<?xml") && head.contains("<svg"))
+}
+
+/// Render `data` into a `px`×`px` PNG, scaled to fit and centered.
+pub fn rasterize(data: &[u8], px: u32) -> Result<Vec<u8>> {
+    let tree =
+        Tree::from_data(data, &Options::default()).map_err(|e| anyhow!("invalid SVG: {}", e))?;
+    let mut pixmap = Pixmap::new(px, px).ok_or_else(|| anyhow!("invalid raster size {}px", px))?;
+    let size = tree.size();
+    let scale = px as f32 / size.width().max(size.height());
+    let transform = Transform::from_scale(scale, scale).post_translate(
+        (px as f32 - size.width() * scale) / 2.0,
+        (px as f32 - size.height() * scale) / 2.0,
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    pixmap
+        .encode_png()
+        .map_err(|e| anyhow!("PNG encoding failed: {}", e))
+}
+
+/// Decode any stored icon (raster or SVG) to RGBA; SVG is rendered at `px`.
+pub fn decode_icon(data: &[u8], mime: Option<&str>, px: u32) -> Result<image::RgbaImage> {
+    let img = if is_svg(mime, "", data) {
+        image::load_from_memory(&rasterize(data, px)?)?
+    } else {
+        image::load_from_memory(data)?
+    };
+    Ok(img.to_rgba8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BANNER: &str = r##"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10">
+  <rect width="20" height="10" fill="#dea584"/>
+</svg>"##;
+
+    #[test]
+    fn test_is_svg_by_mime_extension_and_content() {
+        assert!(is_svg(Some("image/svg+xml"), "https://a.test/i", b""));
+        assert!(is_svg(None, "https://a.test/icon.SVG?v=2", b""));
+        assert!(is_svg(
+            Some("text/plain"),
+            "https://a.test/i",
+            BANNER.as_bytes()
+        ));
+        assert!(!is_svg(
+            Some("image/png"),
+            "https://a.test/i.png",
+            b"\x89PNG"
+        ));
+        assert!(!is_svg(None, "https://a.test/page", b"<html><svg/></html>"));
+    }
+
+    #[test]
+    fn test_rasterize_fits_and_centers() -> Result<()> {
+        for px in RASTER_SIZES {
+            let png = rasterize(BANNER.as_bytes(), px)?;
+            let img = image::load_from_memory(&png)?.to_rgba8();
+            assert_eq!(img.dimensions(), (px, px));
+            // 2:1 artwork: centre filled, top and bottom quarters transparent
+            assert_eq!(img.get_pixel(px / 2, px / 2).0, [0xde, 0xa5, 0x84, 0xff]);
+            assert_eq!(img.get_pixel(px / 2, 0).0[3], 0);
+            assert_eq!(img.get_pixel(px / 2, px - 1).0[3], 0);
+        }
+        assert!(rasterize(b"<svg", 16).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_icon_handles_both_formats() -> Result<()> {
+        let svg = decode_icon(BANNER.as_bytes(), Some(SVG_MIME), 32)?;
+        assert_eq!(svg.dimensions(), (32, 32));
+        let mut png = Vec::new();
+        image::RgbaImage::new(8, 8)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        assert_eq!(
+            decode_icon(&png, Some("image/png"), 32)?.dimensions(),
+            (8, 8)
+        );
+        Ok(())
+    }
+}
@@ -1,7 +1,6 @@
 use anyhow::Result;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use once_cell::sync::OnceCell;
-use std::thread;
 use std::time::Duration;
 
 // URL record type not referenced in this module
@@ -13,98 +12,227 @@ pub enum TrayEvent {
     Add,
     Quit,
     OpenUrl(i64), // open URL by id from history
+    /// Global shortcuts (see `crate::hotkeys`).
+    Launcher,
+    AddClipboard,
 }
 
 static TRAY_RX: OnceCell<Receiver<TrayEvent>> = OnceCell::new();
 static TRAY_TX: OnceCell<Sender<TrayEvent>> = OnceCell::new();
 
+/// Entries shown in the "Webapps" recent section and in "Historique".
+const RECENT_ITEMS: i64 = 5;
+
+/// One URL in a tray submenu.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuEntry {
+    pub url_id: i64,
+    pub label: String,
+    pub icon_mime: Option<String>,
+    pub icon_data: Option<Vec<u8>>,
+}
+
+#[cfg_attr(not(feature = "real_tray"), allow(dead_code))]
+impl MenuEntry {
+    fn from_record(rec: crate::db::UrlRecord) -> Self {
+        let display = rec.site_name.unwrap_or(rec.label);
+        MenuEntry {
+            url_id: rec.id,
+            label: format!("{} ({})", display, rec.url),
+            icon_mime: rec.icon_mime,
+            icon_data: rec.icon_data,
+        }
+    }
+
+    pub fn event(&self) -> TrayEvent {
+        TrayEvent::OpenUrl(self.url_id)
+    }
+}
+
+/// Content of the dynamic submenus, independent of the tray backend so it
+/// can be rebuilt, compared and tested without a real tray.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MenuModel {
+    /// "Webapps": favorites, then a separator, then the most recent URLs.
+    pub favorites: Vec<MenuEntry>,
+    pub recent: Vec<MenuEntry>,
+    /// "Tags": one submenu per tag.
+    pub tags: Vec<(String, Vec<MenuEntry>)>,
+    /// "Historique": most frecent URLs first.
+    pub history: Vec<MenuEntry>,
+}
+
+#[cfg_attr(not(feature = "real_tray"), allow(dead_code))]
+impl MenuModel {
+    /// Current tray content. Call again after `DbHandle::subscribe` reports a change.
+    pub fn load(db: &crate::db::DbHandle) -> Result<Self> {
+        let entries = |list: Vec<crate::db::UrlRecord>| -> Vec<MenuEntry> {
+            list.into_iter().map(MenuEntry::from_record).collect()
+        };
+        let mut tags = Vec::new();
+        for tag in db.list_tags()? {
+            let list = db.list_by_tag(&tag.name)?;
+            tags.push((tag.name, entries(list)));
+        }
+        Ok(MenuModel {
+            favorites: entries(db.list_favorites()?),
+            recent: entries(db.list_recent(RECENT_ITEMS)?),
+            tags,
+            history: entries(db.list_frecent(RECENT_ITEMS)?),
+        })
+    }
+}
+
 #[cfg(feature = "real_tray")]
 mod real_tray {
     use super::*;
     use image::io::Reader as ImageReader;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
     use std::fs;
     use std::path::Path;
-    use tray_icon::menu::{Menu, MenuId, MenuItem, PredefinedMenuItem, Submenu};
+    use tray_icon::menu::{
+        Icon as MenuIcon, IconMenuItem, Menu, MenuId, MenuItem, PredefinedMenuItem, Submenu,
+    };
     use tray_icon::Icon as TrayIconIcon;
     use tray_icon::TrayIconBuilder;
-    // Arc not needed here
 
-    pub fn start_real_tray(db: crate::db::DbHandle) -> Result<()> {
-        let tx = TRAY_TX
-            .get()
-            .expect("TRAY_TX must be set before starting real tray")
-            .clone();
+    /// Ids of the fixed menu items.
+    const SHOW_ID: &str = "show";
+    const ADD_ID: &str = "add";
+    const QUIT_ID: &str = "quit";
 
-        // Build the tray on the current (main) thread. This avoids creating
-        // a platform event loop off the main thread (Windows panics otherwise).
-        // We keep the TrayIcon alive for the process lifetime by leaking it.
-        let menu = Menu::new();
-        let show_item = MenuItem::new("Voir les URLs", true, None);
-        let add_item = MenuItem::new("Ajouter URL", true, None);
-        let history_submenu = Submenu::new("Historique", true);
+    struct DynamicMenus {
+        /// Owning the icon keeps it in the system tray; dropping it removes it.
+        _icon: tray_icon::TrayIcon,
+        webapps: Submenu,
+        history: Submenu,
+        tags: Submenu,
+        /// The `TrayEvent` of each URL item.
+        id_map: HashMap<MenuId, TrayEvent>,
+        /// What the submenus currently show, to skip no-op rebuilds.
+        model: MenuModel,
+    }
 
-        let webapps_submenu = Submenu::new("Webapps", true);
+    thread_local! {
+        // Menu handles are not `Send`: they stay on the main thread that built the tray.
+        static MENUS: RefCell<Option<DynamicMenus>> = const { RefCell::new(None) };
+    }
 
-        let mut id_map: std::collections::HashMap<MenuId, TrayEvent> =
-            std::collections::HashMap::new();
-
-        if let Ok(list) = db.list_recent(5) {
-            for rec in list {
-                let display = rec.site_name.clone().unwrap_or_else(|| rec.label.clone());
-                let label = format!("{} ({})", display, rec.url);
-                let item = MenuItem::new(&label, true, None);
-                let item_id = item.id();
-                id_map.insert(item_id.clone(), TrayEvent::OpenUrl(rec.id));
-                let _ = history_submenu.append_items(&[&item]);
-                println!(
-                    "[tray] added history menu item id={:?} -> url id={}",
-                    item_id, rec.id
-                );
-            }
+    /// Menu icon from the entry's icon, which the DB already picked for 16px.
+    fn menu_icon(entry: &MenuEntry) -> Option<MenuIcon> {
+        let img =
+            crate::svg::decode_icon(entry.icon_data.as_deref()?, entry.icon_mime.as_deref(), 16)
+                .ok()?;
+        let rgba = image::imageops::resize(&img, 16, 16, image::imageops::FilterType::Lanczos3);
+        MenuIcon::from_rgba(rgba.into_raw(), 16, 16).ok()
+    }
+
+    fn url_item(entry: &MenuEntry, id_map: &mut HashMap<MenuId, TrayEvent>) -> IconMenuItem {
+        let item = IconMenuItem::new(&entry.label, true, menu_icon(entry), None);
+        id_map.insert(item.id().clone(), entry.event());
+        item
+    }
+
+    fn fill(submenu: &Submenu, entries: &[MenuEntry], id_map: &mut HashMap<MenuId, TrayEvent>) {
+        for entry in entries {
+            let _ = submenu.append_items(&[&url_item(entry, id_map)]);
         }
+    }
 
-        // Build Webapps submenu: favourites first, then recent
-        if let Ok(favs) = db.list_favorites() {
-            if !favs.is_empty() {
-                for rec in favs {
-                    let display = rec.site_name.clone().unwrap_or_else(|| rec.label.clone());
-                    let label = format!("{} ({})", display, rec.url);
-                    let item = MenuItem::new(&label, true, None);
-                    let item_id = item.id();
-                    id_map.insert(item_id.clone(), TrayEvent::OpenUrl(rec.id));
-                    let _ = webapps_submenu.append_items(&[&item]);
-                    println!(
-                        "[tray] added favourite webapp item id={:?} -> url id={}",
-                        item_id, rec.id
-                    );
-                }
-                let _ = webapps_submenu.append_items(&[&PredefinedMenuItem::separator()]);
-            }
+    /// Replace the content of the dynamic submenus with `model`.
+    fn render(
+        webapps: &Submenu,
+        tags: &Submenu,
+        history: &Submenu,
+        model: &MenuModel,
+    ) -> HashMap<MenuId, TrayEvent> {
+        while webapps.remove_at(0).is_some() {}
+        while history.remove_at(0).is_some() {}
+        while tags.remove_at(0).is_some() {}
+
+        let mut id_map = HashMap::new();
+        fill(webapps, &model.favorites, &mut id_map);
+        if !model.favorites.is_empty() && !model.recent.is_empty() {
+            let _ = webapps.append_items(&[&PredefinedMenuItem::separator()]);
+        }
+        fill(webapps, &model.recent, &mut id_map);
+        for (name, entries) in &model.tags {
+            let tag_submenu = Submenu::new(name, true);
+            fill(&tag_submenu, entries, &mut id_map);
+            let _ = tags.append_items(&[&tag_submenu]);
         }
+        tags.set_enabled(!model.tags.is_empty());
+        fill(history, &model.history, &mut id_map);
+        id_map
+    }
 
-        if let Ok(list2) = db.list_recent(5) {
-            if !list2.is_empty() {
-                for rec in list2 {
-                    let display = rec.site_name.clone().unwrap_or_else(|| rec.label.clone());
-                    let label = format!("{} ({})", display, rec.url);
-                    let item = MenuItem::new(&label, true, None);
-                    let item_id = item.id();
-                    id_map.insert(item_id.clone(), TrayEvent::OpenUrl(rec.id));
-                    let _ = webapps_submenu.append_items(&[&item]);
-                    println!(
-                        "[tray] added recent webapp item id={:?} -> url id={}",
-                        item_id, rec.id
-                    );
-                }
+    /// Rebuild the "Webapps", "Tags" and "Historique" submenus from the current DB state.
+    /// Must run on the thread that called `start_real_tray`.
+    pub fn refresh_menus(db: &crate::db::DbHandle) -> Result<()> {
+        let model = MenuModel::load(db)?;
+        MENUS.with(|menus| {
+            let mut menus = menus.borrow_mut();
+            let menus = menus
+                .as_mut()
+                .ok_or_else(|| anyhow::anyhow!("tray menus not built on this thread"))?;
+            if menus.model == model {
+                return Ok(());
             }
+            menus.id_map = render(&menus.webapps, &menus.tags, &menus.history, &model);
+            menus.model = model;
+            println!("[tray] menus rebuilt");
+            Ok(())
+        })
+    }
+
+    /// Remove the tray icon and drop its menus. Must run on the thread that
+    /// called `start_real_tray`.
+    pub fn shutdown_menus() {
+        if MENUS.with(|menus| menus.borrow_mut().take()).is_some() {
+            println!("[tray] tray icon removed");
+        }
+    }
+
+    /// The `TrayEvent` for a click on the menu item `id`. Must run on the
+    /// thread that called `start_real_tray`.
+    pub fn event_for(id: &str) -> Option<TrayEvent> {
+        match id {
+            SHOW_ID => Some(TrayEvent::Show),
+            ADD_ID => Some(TrayEvent::Add),
+            QUIT_ID => Some(TrayEvent::Quit),
+            _ => MENUS.with(|menus| {
+                let menus = menus.borrow();
+                menus.as_ref()?.id_map.get(&MenuId::new(id)).cloned()
+            }),
         }
+    }
+
+    pub fn start_real_tray(db: crate::db::DbHandle) -> Result<()> {
+        // Build the tray on the current (main) thread. This avoids creating
+        // a platform event loop off the main thread (Windows panics otherwise).
+        // The TrayIcon lives in `MENUS` until `shutdown_menus` drops it.
+        let menu = Menu::new();
+        let show_item = MenuItem::with_id(SHOW_ID, "Voir les URLs", true, None);
+        let add_item = MenuItem::with_id(ADD_ID, "Ajouter URL", true, None);
+        let history_submenu = Submenu::new("Historique", true);
+
+        let webapps_submenu = Submenu::new("Webapps", true);
+        let tags_submenu = Submenu::new("Tags", true);
+
+        let model = MenuModel::load(&db).unwrap_or_else(|e| {
+            eprintln!("[tray] failed to load menu entries: {}", e);
+            MenuModel::default()
+        });
+        let id_map = render(&webapps_submenu, &tags_submenu, &history_submenu, &model);
 
-        let quit_item = MenuItem::new("Quitter Rustine", true, None);
+        let quit_item = MenuItem::with_id(QUIT_ID, "Quitter Rustine", true, None);
         let _ = menu.append_items(&[
             &show_item,
             &add_item,
             &PredefinedMenuItem::separator(),
             &webapps_submenu,
+            &tags_submenu,
             &PredefinedMenuItem::separator(),
             &history_submenu,
             &PredefinedMenuItem::separator(),
@@ -182,43 +310,19 @@ mod real_tray {
 
         let tray = builder.build()?;
 
-        // Keep the tray alive for the process lifetime.
-        let _static_tray_ref: &'static tray_icon::TrayIcon = Box::leak(Box::new(tray));
-
-        println!("[tray] tray icon created on main thread and leaked to static lifetime");
-
-        // Receiver for menu events (the crate will forward events when the
-        // platform/main event loop runs, e.g., after the GUI is launched).
-        let menu_rx = tray_icon::menu::MenuEvent::receiver();
-
-        // Move id_map and the item ids into a background thread that will
-        // forward menu events into our application channel. The actual menu
-        // dispatch is performed by the global event loop, so reading from
-        // `menu_rx` here is safe.
-        let show_id = show_item.id().clone();
-        let add_id = add_item.id().clone();
-        let quit_id = quit_item.id().clone();
-        let id_map = std::sync::Arc::new(id_map);
-
-        thread::spawn(move || {
-            while let Ok(ev) = menu_rx.recv() {
-                println!("[tray] menu event id={:?}", ev.id);
-                if let Some(action) = id_map.get(&ev.id) {
-                    println!("[tray] mapped history item -> {:?}", action);
-                    let _ = tx.send(action.clone());
-                } else if ev.id == show_id {
-                    println!("[tray] Show clicked");
-                    let _ = tx.send(TrayEvent::Show);
-                } else if ev.id == add_id {
-                    println!("[tray] Add clicked");
-                    let _ = tx.send(TrayEvent::Add);
-                } else if ev.id == quit_id {
-                    println!("[tray] Quit clicked");
-                    let _ = tx.send(TrayEvent::Quit);
-                } else {
-                    println!("[tray] Unhandled menu event: {:?}", ev.id);
-                }
-            }
+        println!("[tray] tray icon created on main thread");
+
+        // Clicks reach `dispatch_menu_event` through the desktop event loop,
+        // which installs the menu event handler once it runs.
+        MENUS.with(|menus| {
+            *menus.borrow_mut() = Some(DynamicMenus {
+                _icon: tray,
+                webapps: webapps_submenu,
+                history: history_submenu,
+                tags: tags_submenu,
+                id_map,
+                model,
+            });
         });
 
         Ok(())
@@ -246,13 +350,58 @@ pub fn start_tray(db: crate::db::DbHandle) -> Result<()> {
     }
 }
 
+/// Rebuild the dynamic tray submenus after the library changed (the UI calls
+/// this on every burst of `DbHandle::subscribe` events). Must be called from the main thread
+/// (where the tray was created); a no-op without `real_tray`.
+pub fn refresh_tray(db: &crate::db::DbHandle) -> Result<()> {
+    #[cfg(feature = "real_tray")]
+    {
+        return real_tray::refresh_menus(db);
+    }
+
+    #[cfg(not(feature = "real_tray"))]
+    {
+        let _ = db;
+        Ok(())
+    }
+}
+
+/// Turn a click on the tray menu item `id` into a `TrayEvent` on the tray
+/// channel. Once the desktop event loop runs it owns the menu event handler,
+/// so the UI forwards the clicks it sees here (`ui::app`). Must be called from
+/// the main thread; a no-op without `real_tray`.
+pub fn dispatch_menu_event(id: &str) -> Result<()> {
+    #[cfg(feature = "real_tray")]
+    {
+        return match real_tray::event_for(id) {
+            Some(ev) => send_event(ev),
+            None => {
+                println!("[tray] Unhandled menu event: {:?}", id);
+                Ok(())
+            }
+        };
+    }
+
+    #[cfg(not(feature = "real_tray"))]
+    {
+        let _ = id;
+        Ok(())
+    }
+}
+
+/// Remove the tray icon, e.g. when quitting. Must be called from the main
+/// thread (where the tray was created); a no-op without `real_tray`.
+pub fn shutdown_tray() {
+    #[cfg(feature = "real_tray")]
+    real_tray::shutdown_menus();
+}
+
 /// Returns a clone of the `Receiver<TrayEvent>` if the tray has been started.
 pub fn get_receiver() -> Option<Receiver<TrayEvent>> {
     TRAY_RX.get().cloned()
 }
 
 /// Send a tray event programmatically (useful for testing or hooking real menu callbacks).
-#[allow(dead_code)]
 pub fn send_event(ev: TrayEvent) -> Result<()> {
     if let Some(tx) = TRAY_TX.get() {
         tx.send(ev)
@@ -262,3 +411,64 @@ pub fn send_event(ev: TrayEvent) -> Result<()> {
         Err(anyhow::anyhow!("tray not started"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn ids(entries: &[MenuEntry]) -> Vec<i64> {
+        entries.iter().map(|e| e.url_id).collect()
+    }
+
+    #[test]
+    fn test_menu_model_follows_the_library() -> Result<()> {
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        assert_eq!(MenuModel::load(&db)?, MenuModel::default());
+
+        let docs = db.insert_url("Docs", "https://docs.rs/", 1)?;
+        let rust = db.insert_url("Rust", "https://www.rust-lang.org/", 2)?;
+        let model = MenuModel::load(&db)?;
+        assert!(model.favorites.is_empty());
+        assert_eq!(ids(&model.recent), vec![rust, docs]);
+        assert_eq!(model.recent[0].label, "Rust (https://www.rust-lang.org/)");
+        assert!(matches!(model.recent[0].event(), TrayEvent::OpenUrl(id) if id == rust));
+
+        // favorites, tags and deletions show up on the next load
+        db.add_favorite(docs)?;
+        db.tag_url(rust, "Lang")?;
+        let model = MenuModel::load(&db)?;
+        assert_eq!(ids(&model.favorites), vec![docs]);
+        assert_eq!(model.tags.len(), 1);
+        assert_eq!(model.tags[0].0, "Lang");
+        assert_eq!(ids(&model.tags[0].1), vec![rust]);
+
+        db.delete(rust)?;
+        let model = MenuModel::load(&db)?;
+        assert_eq!(ids(&model.recent), vec![docs]);
+        assert!(model.tags.is_empty());
+        assert_eq!(ids(&model.history), vec![docs]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_menu_model_limits_recent_and_prefers_site_names() -> Result<()> {
+        let db = crate::db::init_db(Path::new(":memory:"))?;
+        for i in 0..8 {
+            db.insert_url(
+                &format!("Site {}", i),
+                &format!("https://s{}.example/", i),
+                i,
+            )?;
+        }
+        db.upsert_site_meta("https://s7.example:443", Some("Seven"), None, None, None)?;
+        let model = MenuModel::load(&db)?;
+        assert_eq!(model.recent.len(), RECENT_ITEMS as usize);
+        assert_eq!(model.history.len(), RECENT_ITEMS as usize);
+        assert_eq!(model.recent[0].label, "Seven (https://s7.example/)");
+
+        // a reload without changes compares equal, so the tray skips the rebuild
+        assert_eq!(MenuModel::load(&db)?, model);
+        Ok(())
+    }
+}
@@ -1,13 +1,73 @@
+use crate::tray::TrayEvent;
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use chrono::Utc;
 use dioxus::prelude::use_coroutine;
 use dioxus::prelude::*;
+use dioxus_desktop::tao::event::Event;
+use dioxus_desktop::{DesktopContext, WindowBuilder, WindowCloseBehaviour, WindowEvent};
+use futures::channel::mpsc::UnboundedSender;
 use futures::stream::StreamExt;
+use futures::FutureExt;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs;
+use std::rc::{Rc, Weak};
 use url::Url;
 // use crate::webview; -- calling via `crate::webview::open_url(...)`
 
+/// Context of the resident window: started with `--minimized`.
+#[derive(Clone, Copy)]
+struct StartMinimized(bool);
+
+/// Context of the main window: the request to open it with.
+#[derive(Clone)]
+struct InitialRequest(Option<MainRequest>);
+
+/// Requests from the resident window to the open main window.
+#[derive(Clone)]
+enum MainRequest {
+    QuickAdd(String, String),
+    Launcher,
+    Status(String),
+    Error(String),
+}
+
+/// The main window, registered by `root` when it mounts.
+struct MainWindow {
+    window: Weak<dioxus_desktop::DesktopService>,
+    tx: UnboundedSender<MainRequest>,
+}
+
+thread_local! {
+    // Both windows run on the main thread.
+    static MAIN_WINDOW: RefCell<Option<MainWindow>> = const { RefCell::new(None) };
+}
+
+/// The main window if it is open, and the channel to its UI.
+fn main_window() -> Option<(DesktopContext, UnboundedSender<MainRequest>)> {
+    MAIN_WINDOW.with(|main| {
+        let main = main.borrow();
+        let main = main.as_ref()?;
+        Some((main.window.upgrade()?, main.tx.clone()))
+    })
+}
+
+/// Run the UI until "Quit". The launched window is the hidden resident one
+/// (`app`), which lives as long as the process; the main window (`root`) is a
+/// second window it opens, at startup unless `start_minimized`.
+pub fn launch(start_minimized: bool) -> ! {
+    let config = dioxus_desktop::Config::new()
+        .with_window(
+            WindowBuilder::new()
+                .with_title("Rustine")
+                .with_visible(false),
+        )
+        .with_close_behaviour(WindowCloseBehaviour::WindowHides);
+    let dom = VirtualDom::new(app).with_root_context(StartMinimized(start_minimized));
+    dioxus_desktop::launch::launch_virtual_dom(dom, config)
+}
+
 fn root() -> Element {
     let mut urls = use_signal(|| Vec::<crate::db::UrlRecord>::new());
 
@@ -15,10 +75,69 @@ fn root() -> Element {
     let mut label_input = use_signal(|| String::new());
     let mut url_input = use_signal(|| String::new());
     let mut error_msg = use_signal(|| String::new());
+    // outcome of the last bookmark import/export
+    let mut status_msg = use_signal(|| String::new());
 
     // selected screen for simple in-app navigation (None => list view)
     let mut selected_screen = use_signal(|| Option::<i64>::None);
 
+    // list filter: show favorites only
+    let mut favorites_only = use_signal(|| false);
+    // list filter: only rows whose last link check failed
+    let mut broken_only = use_signal(|| false);
+    // "Check links" is running
+    let mut checking_links = use_signal(|| false);
+    // a bookmark import is running
+    let mut importing = use_signal(|| false);
+
+    // search box and the highlighted snippet of each hit, keyed by row id
+    let mut search_query = use_signal(|| String::new());
+    let mut snippets = use_signal(|| HashMap::<i64, String>::new());
+
+    // tag sidebar: all tags and the one currently filtering the list
+    let mut tags = use_signal(|| Vec::<crate::db::Tag>::new());
+    let mut tag_filter = use_signal(|| Option::<String>::None);
+
+    // edit form state for the details screen
+    let mut edit_label = use_signal(|| String::new());
+    let mut edit_url = use_signal(|| String::new());
+    let mut tag_input = use_signal(|| String::new());
+    let mut details = use_signal(|| Option::<crate::db::UrlRecord>::None);
+    // metadata of the site the details screen's URL belongs to
+    let mut details_site = use_signal(|| Option::<crate::db::SiteMeta>::None);
+    // latest link checks of the details screen's URL, newest first
+    let mut details_links = use_signal(|| Vec::<crate::db::LinkCheck>::new());
+
+    let initial = use_hook(|| try_consume_context::<InitialRequest>().and_then(|r| r.0));
+
+    // tray "Add" dialog as (label, url); None while it is closed
+    let mut quick_add = use_signal(|| match initial.clone() {
+        Some(MainRequest::QuickAdd(label, url)) => Some((label, url)),
+        _ => None,
+    });
+
+    // launcher (global shortcut) query and its hits; None while it is closed
+    let mut launcher = use_signal(|| match initial {
+        Some(MainRequest::Launcher) => Some(String::new()),
+        _ => None,
+    });
+    let mut launcher_hits = use_signal(|| match launcher.peek().as_deref() {
+        Some(query) => launcher_results(query),
+        None => Vec::new(),
+    });
+
+    // "Settings" screen: window toggles, edited bindings and what failed to bind
+    let mut show_settings = use_signal(|| false);
+    let mut window_settings = use_signal(crate::config::WindowConfig::default);
+    let mut hotkey_form = use_signal(crate::config::HotkeyConfig::default);
+    let mut hotkey_conflicts = use_signal(Vec::<crate::hotkeys::Conflict>::new);
+
+    // "Find duplicates" report; None while it is closed
+    let mut duplicates = use_signal(|| Option::<Vec<crate::db::DuplicateGroup>>::None);
+
+    // background metadata jobs (new rows and manual refreshes), keyed by row id
+    let mut meta_jobs = use_signal(|| HashMap::<i64, MetaJob>::new());
+
     // Load compiled Tailwind CSS from assets at runtime and inject into the page.
     let style_css = use_signal(|| String::new());
     {
@@ -29,118 +148,426 @@ fn root() -> Element {
         });
     }
 
-    // tray -> UI: forward TrayEvent into a dioxus coroutine so UI/main-thread
-    // code (not a background thread) performs actions like opening URLs.
-    let (err_tx, err_rx) = futures::channel::mpsc::unbounded::<String>();
-
-    // Create an async channel that is safe to send from other threads.
-    let (tray_async_tx, tray_async_rx) =
-        futures::channel::mpsc::unbounded::<crate::tray::TrayEvent>();
-
-    // Background thread: read the crossbeam receiver and forward into the async channel.
-    if let Some(rx) = crate::tray::get_receiver() {
-        let tx = tray_async_tx.clone();
-        std::thread::spawn(move || {
-            while let Ok(ev) = rx.recv() {
-                let _ = tx.unbounded_send(ev);
-            }
+    // requests from the resident window (tray "Add", global shortcuts, tray
+    // errors) while this window is open
+    let requests = use_hook(|| {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<MainRequest>();
+        MAIN_WINDOW.with(|main| {
+            *main.borrow_mut() = Some(MainWindow {
+                window: Rc::downgrade(&dioxus_desktop::window()),
+                tx,
+            });
         });
-    }
-
-    // Consume the async tray events on the UI's async context so UI/main-thread
-    // operations (like opening a webview) are executed in the right context.
-    let mut tray_rx_opt = Some(tray_async_rx);
+        Rc::new(RefCell::new(Some(rx)))
+    });
     use_future(move || {
-        let err_tx = err_tx.clone();
-        let tray_rx_opt = tray_rx_opt.take();
+        let rx = requests.borrow_mut().take();
         async move {
-            if let Some(mut rx) = tray_rx_opt {
-                while let Some(ev) = rx.next().await {
-                    match ev {
-                        crate::tray::TrayEvent::OpenUrl(id) => {
-                            if let Some(db) = crate::db::get_global() {
-                                match db.get_by_id(id) {
-                                    Ok(Some(rec)) => {
-                                        let u = rec.url.clone();
-                                        if let Err(e) = crate::webview::open_url(u) {
-                                            let _ = err_tx.unbounded_send(format!(
-                                                "Erreur ouverture URL (tray): {}",
-                                                e
-                                            ));
-                                        }
-                                    }
-                                    Ok(None) => {
-                                        let _ = err_tx
-                                            .unbounded_send(format!("URL introuvable (id={})", id));
-                                    }
-                                    Err(e) => {
-                                        let _ = err_tx
-                                            .unbounded_send(format!("Erreur DB (tray): {}", e));
-                                    }
-                                }
-                            } else {
-                                let _ = err_tx
-                                    .unbounded_send("Base de données non disponible".to_string());
-                            }
-                        }
-                        crate::tray::TrayEvent::Show => {
-                            let _ = err_tx.unbounded_send("Tray: show clicked".to_string());
-                        }
-                        crate::tray::TrayEvent::Add => {
-                            let _ = err_tx.unbounded_send("Tray: add clicked".to_string());
-                        }
-                        crate::tray::TrayEvent::Quit => {
-                            let _ = err_tx.unbounded_send("Tray: quit requested".to_string());
-                        }
+            let Some(mut rx) = rx else {
+                return;
+            };
+            while let Some(req) = rx.next().await {
+                match req {
+                    MainRequest::QuickAdd(label, url) => {
+                        error_msg.set(String::new());
+                        quick_add.set(Some((label, url)));
+                    }
+                    MainRequest::Launcher => {
+                        launcher_hits.set(launcher_results(""));
+                        launcher.set(Some(String::new()));
                     }
+                    MainRequest::Status(msg) => status_msg.set(msg),
+                    MainRequest::Error(msg) => error_msg.set(msg),
                 }
             }
         }
     });
 
-    // Consume error messages on the UI async context and set the signal
-    let mut err_rx_opt = Some(err_rx);
-
-    use_future(move || {
-        let err_rx_opt = err_rx_opt.take();
-        async move {
-            if let Some(mut rx) = err_rx_opt {
-                while let Some(msg) = rx.next().await {
-                    error_msg.set(msg);
-                }
+    // Closing this window drops its UI; the app stays in the tray unless
+    // `close_to_tray` is off.
+    dioxus_desktop::use_wry_event_handler(move |event, _| {
+        if let Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } = event
+        {
+            if !crate::config::current().window.close_to_tray {
+                let _ = crate::tray::send_event(TrayEvent::Quit);
             }
         }
     });
 
     // Coroutine for async DB actions (created after signals so it can capture them)
     let db_coroutine = use_coroutine(move |mut rx| async move {
+        let mut reload = move |db: &crate::db::DbHandle| {
+            let filter = ListFilter {
+                favorites_only: favorites_only.with(|f| *f),
+                broken_only: broken_only.with(|b| *b),
+                tag: tag_filter.with(|t| t.clone()),
+                query: search_query.with(|q| q.clone()),
+            };
+            if let Ok((list, found)) = load_list(db, &filter) {
+                urls.set(list);
+                snippets.set(found);
+            }
+            if let Ok(all) = db.list_tags() {
+                tags.set(all);
+            }
+        };
+        // Fetch metadata for row `id` on a worker thread, showing progress on the row.
+        let mut start_meta_job =
+            move |db: &crate::db::DbHandle,
+                  id: i64,
+                  url: String,
+                  reason: crate::metadata::FetchReason| {
+                meta_jobs.write().insert(id, MetaJob::Fetching);
+                let (done_tx, done_rx) = futures::channel::oneshot::channel();
+                let worker_db = db.clone();
+                std::thread::spawn(move || {
+                    let _ =
+                        done_tx.send(crate::metadata::fetch_and_store(&worker_db, &url, reason));
+                });
+                spawn(async move {
+                    match done_rx.await {
+                        Ok(Ok(Some(_))) => {
+                            meta_jobs.write().remove(&id);
+                        }
+                        Ok(Ok(None)) => {
+                            meta_jobs.write().remove(&id);
+                            if reason == crate::metadata::FetchReason::Manual {
+                                status_msg.set(
+                                    "Not fetched: metadata fetching is off for this URL or host"
+                                        .to_string(),
+                                );
+                            }
+                        }
+                        Ok(Err(e)) => {
+                            eprintln!("metadata fetch for id={} failed: {}", id, e);
+                            meta_jobs.write().insert(id, MetaJob::Failed(e.to_string()));
+                        }
+                        Err(_) => {
+                            meta_jobs
+                                .write()
+                                .insert(id, MetaJob::Failed("interrupted".to_string()));
+                        }
+                    }
+                });
+            };
         while let Some(action) = rx.next().await {
             match action {
                 DbAction::Load => {
                     if let Some(db) = crate::db::get_global() {
-                        if let Ok(list) = db.list_recent(100) {
-                            urls.set(list);
-                        }
+                        reload(&db);
                     }
                 }
                 DbAction::Delete(id) => {
                     if let Some(db) = crate::db::get_global() {
                         let _ = db.delete(id);
-                        if let Ok(list) = db.list_recent(100) {
-                            urls.set(list);
+                        meta_jobs.write().remove(&id);
+                    }
+                }
+                DbAction::SetFavorite(id, favorite) => {
+                    if let Some(db) = crate::db::get_global() {
+                        let res = if favorite {
+                            db.add_favorite(id)
+                        } else {
+                            db.remove_favorite(id)
+                        };
+                        if let Err(e) = res {
+                            error_msg.set(format!("Erreur DB: {}", e));
                         }
                     }
                 }
-                DbAction::Insert(lab, urlv, ts) => {
+                DbAction::SetNoFetch(id, no_fetch) => {
                     if let Some(db) = crate::db::get_global() {
-                        match db.insert_url(&lab, &urlv, ts) {
+                        if let Err(e) = db.set_no_fetch(id, no_fetch) {
+                            error_msg.set(format!("Erreur DB: {}", e));
+                        }
+                    }
+                }
+                DbAction::Tag(id, name) => {
+                    if let Some(db) = crate::db::get_global() {
+                        match db.tag_url(id, &name) {
                             Ok(()) => {
-                                // clear any previous error and refresh
                                 error_msg.set(String::new());
-                                if let Ok(list) = db.list_recent(100) {
-                                    urls.set(list);
+                                tag_input.set(String::new());
+                            }
+                            Err(e) => {
+                                error_msg.set(format!("Erreur DB: {}", e));
+                            }
+                        }
+                    }
+                }
+                DbAction::Untag(id, name) => {
+                    if let Some(db) = crate::db::get_global() {
+                        if let Err(e) = db.untag_url(id, &name) {
+                            error_msg.set(format!("Erreur DB: {}", e));
+                        }
+                    }
+                }
+                DbAction::ImportBookmarks => {
+                    if importing.with(|i| *i) {
+                        continue;
+                    }
+                    let Some(db) = crate::db::get_global() else {
+                        continue;
+                    };
+                    // Large files take a while: import off the UI thread, and
+                    // detached so the other list actions don't wait for it.
+                    importing.set(true);
+                    spawn(async move {
+                        let picked = rfd::AsyncFileDialog::new()
+                            .set_title("Import bookmarks")
+                            .add_filter("Bookmarks", &["html", "htm"])
+                            .pick_file()
+                            .await;
+                        if let Some(file) = picked {
+                            let path = file.path().to_path_buf();
+                            status_msg.set("Importing bookmarks…".to_string());
+                            let (done_tx, done_rx) = futures::channel::oneshot::channel();
+                            std::thread::spawn(move || {
+                                let res = fs::read_to_string(&path)
+                                    .map_err(anyhow::Error::from)
+                                    .and_then(|html| {
+                                        crate::bookmarks::import_netscape(
+                                            &db,
+                                            &html,
+                                            crate::bookmarks::FolderTags::All,
+                                        )
+                                    });
+                                let _ = done_tx.send(res);
+                            });
+                            match done_rx.await {
+                                Ok(Ok(summary)) => {
+                                    error_msg.set(String::new());
+                                    status_msg.set(format!(
+                                        "Import: {} added, {} skipped, {} failed",
+                                        summary.added, summary.skipped, summary.failed
+                                    ));
+                                    for err in &summary.errors {
+                                        eprintln!("bookmark import: {}", err);
+                                    }
+                                }
+                                Ok(Err(e)) => {
+                                    status_msg.set(String::new());
+                                    error_msg.set(format!("Import failed: {}", e));
+                                }
+                                Err(_) => {
+                                    status_msg.set(String::new());
+                                    error_msg.set("Import interrupted".to_string());
                                 }
                             }
+                        }
+                        importing.set(false);
+                    });
+                }
+                DbAction::Export => {
+                    let picked = rfd::AsyncFileDialog::new()
+                        .set_title("Export library")
+                        .set_file_name("rustine-export.json")
+                        .add_filter("JSON", &["json"])
+                        .add_filter("CSV", &["csv"])
+                        .add_filter("Bookmarks HTML", &["html", "htm"])
+                        .save_file()
+                        .await;
+                    let path = match picked {
+                        Some(file) => file.path().to_path_buf(),
+                        None => continue,
+                    };
+                    let format = path
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .and_then(crate::db::ExportFormat::from_extension);
+                    let Some(format) = format else {
+                        error_msg.set("Export: use a .json, .csv or .html file name".to_string());
+                        continue;
+                    };
+                    if let Some(db) = crate::db::get_global() {
+                        let (done_tx, done_rx) = futures::channel::oneshot::channel();
+                        let worker_path = path.clone();
+                        std::thread::spawn(move || {
+                            let res = fs::File::create(&worker_path)
+                                .map_err(anyhow::Error::from)
+                                .and_then(|file| db.export(format, std::io::BufWriter::new(file)));
+                            let _ = done_tx.send(res);
+                        });
+                        // icons make big files: don't hold up the other actions
+                        spawn(async move {
+                            match done_rx.await {
+                                Ok(Ok(())) => {
+                                    error_msg.set(String::new());
+                                    status_msg.set(format!("Exported to {}", path.display()));
+                                }
+                                Ok(Err(e)) => error_msg.set(format!("Export failed: {}", e)),
+                                Err(_) => error_msg.set("Export interrupted".to_string()),
+                            }
+                        });
+                    }
+                }
+                DbAction::RefreshMetadata(id) => {
+                    if let Some(db) = crate::db::get_global() {
+                        if let Ok(Some(rec)) = db.get_by_id(id) {
+                            start_meta_job(&db, id, rec.url, crate::metadata::FetchReason::Manual);
+                        }
+                    }
+                }
+                DbAction::RefreshAllMetadata => {
+                    let policy = crate::config::current().network.fetch_policy;
+                    if policy != crate::config::FetchPolicy::Always {
+                        status_msg.set(
+                            "Background refresh is off under the current network policy"
+                                .to_string(),
+                        );
+                    } else if let Some(db) = crate::db::get_global() {
+                        match db.mark_sites_stale() {
+                            Ok(()) => {
+                                crate::refresh::wake();
+                                status_msg
+                                    .set("Refreshing metadata in the background…".to_string());
+                            }
+                            Err(e) => error_msg.set(format!("Erreur DB: {}", e)),
+                        }
+                    }
+                }
+                DbAction::CheckLink(id) => {
+                    if let Some(db) = crate::db::get_global() {
+                        let Ok(Some(rec)) = db.get_by_id(id) else {
+                            continue;
+                        };
+                        let (done_tx, done_rx) = futures::channel::oneshot::channel();
+                        let worker_db = db.clone();
+                        std::thread::spawn(move || {
+                            let _ = done_tx.send(crate::linkcheck::check_and_record(
+                                &worker_db,
+                                id,
+                                &rec.url,
+                                crate::metadata::FetchReason::Manual,
+                            ));
+                        });
+                        // the probe can take a whole HTTP timeout: don't hold up other actions
+                        spawn(async move {
+                            match done_rx.await {
+                                Ok(Ok(Some(_))) => {
+                                    error_msg.set(String::new());
+                                    if selected_screen.with(|s| *s) == Some(id) {
+                                        details_links.set(load_link_history(id));
+                                    }
+                                }
+                                Ok(Ok(None)) => error_msg.set(
+                                    "Not checked: fetching is off for this URL or host".to_string(),
+                                ),
+                                Ok(Err(e)) => error_msg.set(format!("Link check failed: {}", e)),
+                                Err(_) => error_msg.set("Link check interrupted".to_string()),
+                            }
+                        });
+                    }
+                }
+                DbAction::CheckLinks => {
+                    if checking_links.with(|c| *c) {
+                        continue;
+                    }
+                    if let Some(db) = crate::db::get_global() {
+                        // Probing the whole library takes a while: run it
+                        // detached so the other list actions don't wait for it.
+                        checking_links.set(true);
+                        status_msg.set("Checking links…".to_string());
+                        let (progress_tx, mut progress_rx) = futures::channel::mpsc::unbounded();
+                        let (done_tx, done_rx) = futures::channel::oneshot::channel();
+                        let worker_db = db.clone();
+                        std::thread::spawn(move || {
+                            let _ = done_tx.send(crate::linkcheck::check_due(
+                                &worker_db,
+                                0,
+                                crate::metadata::FetchReason::Manual,
+                                |checked| {
+                                    let _ = progress_tx.unbounded_send(checked);
+                                },
+                            ));
+                        });
+                        spawn(async move {
+                            while let Some(checked) = progress_rx.next().await {
+                                status_msg.set(format!("Checking links… {} checked", checked));
+                            }
+                            match done_rx.await {
+                                Ok(Ok(checked)) => {
+                                    let broken = db.list_broken_links().map_or(0, |l| l.len());
+                                    status_msg.set(format!(
+                                        "Checked {} links, {} broken",
+                                        checked, broken
+                                    ));
+                                }
+                                Ok(Err(e)) => {
+                                    status_msg.set(String::new());
+                                    error_msg.set(format!("Link check failed: {}", e));
+                                }
+                                Err(_) => status_msg.set("Link check interrupted".to_string()),
+                            }
+                            checking_links.set(false);
+                        });
+                    }
+                }
+                DbAction::ApplyRedirect(id, to) => {
+                    if let Some(db) = crate::db::get_global() {
+                        match db.update_url(id, None, Some(&to)) {
+                            Ok(()) => error_msg.set(String::new()),
+                            Err(e) => {
+                                error_msg.set(format!("Erreur DB: {}", e));
+                            }
+                        }
+                    }
+                }
+                DbAction::FindDuplicates => {
+                    if let Some(db) = crate::db::get_global() {
+                        match db.find_duplicates() {
+                            Ok(groups) if groups.is_empty() => {
+                                duplicates.set(None);
+                                status_msg.set("No duplicates found".to_string());
+                            }
+                            Ok(groups) => duplicates.set(Some(groups)),
+                            Err(e) => error_msg.set(format!("Erreur DB: {}", e)),
+                        }
+                    }
+                }
+                DbAction::MergeDuplicates(ids) => {
+                    if let Some(db) = crate::db::get_global() {
+                        match db.merge_urls(&ids) {
+                            Ok(kept) => {
+                                for id in ids.iter().filter(|id| **id != kept) {
+                                    meta_jobs.write().remove(id);
+                                }
+                                status_msg.set(format!("Merged {} entries", ids.len()));
+                                duplicates.set(
+                                    db.find_duplicates()
+                                        .ok()
+                                        .filter(|groups| !groups.is_empty()),
+                                );
+                            }
+                            Err(e) => error_msg.set(format!("Merge failed: {}", e)),
+                        }
+                    }
+                }
+                DbAction::Update(id, lab, urlv) => {
+                    if let Some(db) = crate::db::get_global() {
+                        match db.update_url(id, Some(&lab), Some(&urlv)) {
+                            Ok(()) => {
+                                error_msg.set(String::new());
+                                selected_screen.set(None);
+                            }
+                            Err(e) => {
+                                error_msg.set(format!("Erreur DB: {}", e));
+                            }
+                        }
+                    }
+                }
+                DbAction::Insert(lab, urlv, ts) => {
+                    if let Some(db) = crate::db::get_global() {
+                        match db.insert_url(&lab, &urlv, ts) {
+                            Ok(id) => {
+                                // clear any previous error; the list reloads on the insert event
+                                error_msg.set(String::new());
+                                // pick up name and icon without waiting for the first open
+                                start_meta_job(&db, id, urlv, crate::metadata::FetchReason::Added);
+                            }
                             Err(e) => {
                                 error_msg.set(format!("Erreur DB: {}", e));
                             }
@@ -151,6 +578,35 @@ fn root() -> Element {
         }
     });
 
+    // DB changes -> list: keep an open details screen current and reload the
+    // list, once per burst of events.
+    use_future(move || async move {
+        let Some(db) = crate::db::get_global() else {
+            return;
+        };
+        let mut changes = match db.subscribe() {
+            Ok(sub) => sub.into_stream(),
+            Err(e) => {
+                eprintln!("DB subscription failed: {}", e);
+                return;
+            }
+        };
+        while let Some(first) = changes.next().await {
+            let mut burst = vec![first];
+            while let Some(Some(ev)) = changes.next().now_or_never() {
+                burst.push(ev);
+            }
+            if let Some(id) = selected_screen.with(|s| *s) {
+                if burst.iter().any(|ev| ev.url_id() == Some(id)) {
+                    if let Ok(rec) = db.get_by_id(id) {
+                        details.set(rec);
+                    }
+                }
+            }
+            db_coroutine.send(DbAction::Load);
+        }
+    });
+
     // Load initial list once
     {
         let db_coroutine = db_coroutine.clone();
@@ -169,6 +625,16 @@ fn root() -> Element {
     let mut on_details = {
         let mut selected = selected_screen.clone();
         move |id: i64| {
+            // prefill the edit form from the current list
+            if let Some(rec) = urls.with(|v| v.iter().find(|r| r.id == id).cloned()) {
+                edit_label.set(rec.label.clone());
+                edit_url.set(rec.url.clone());
+                details_site.set(load_site_meta(&rec.url));
+                details.set(Some(rec));
+            }
+            details_links.set(load_link_history(id));
+            tag_input.set(String::new());
+            error_msg.set(String::new());
             selected.set(Some(id));
         }
     };
@@ -177,49 +643,282 @@ fn root() -> Element {
     let current_label = label_input.with(|s| s.clone());
     let current_url = url_input.with(|s| s.clone());
     let current_error = error_msg.with(|s| s.clone());
+    let current_status_msg = status_msg.with(|s| s.clone());
+    let current_favorites_only = favorites_only.with(|f| *f);
+    let current_broken_only = broken_only.with(|b| *b);
+    let current_query = search_query.with(|q| q.clone());
+    let current_snippets = snippets.with(|m| m.clone());
+    let current_meta_jobs = meta_jobs.with(|m| m.clone());
+    let current_duplicates = duplicates.with(|d| d.clone());
+    let current_quick_add = quick_add.with(|q| q.clone());
+    let current_edit_label = edit_label.with(|s| s.clone());
+    let current_edit_url = edit_url.with(|s| s.clone());
+    let current_tags = tags.with(|v| v.clone());
+    let current_tag_filter = tag_filter.with(|t| t.clone());
+    let current_tag_input = tag_input.with(|s| s.clone());
+    let current_details_tags =
+        details.with(|d| d.as_ref().map(|r| r.tags.clone()).unwrap_or_default());
+    let current_details_site = details_site.with(|s| s.clone());
+    let current_details_no_fetch = details.with(|d| d.as_ref().is_some_and(|r| r.no_fetch));
+    let current_details_links = details_links.with(|v| v.clone());
+    let current_launcher = launcher.with(|l| l.clone());
+    let current_launcher_hits = launcher_hits.with(|v| v.clone());
+    let current_show_settings = show_settings.with(|s| *s);
+    let current_window_settings = window_settings.with(|w| w.clone());
+    let current_hotkey_form = hotkey_form.with(|f| f.clone());
+    let current_hotkey_conflicts = hotkey_conflicts.with(|v| v.clone());
+
+    // open a launcher hit and close the launcher
+    let mut open_launcher_hit = move |rec: crate::db::UrlRecord| {
+        launcher.set(None);
+        if let Err(err) = crate::webview::open_saved_url(rec.id, rec.url) {
+            error_msg.set(format!("Error opening URL: {}", err));
+        }
+    };
 
     let style_content = style_css.with(|s| s.clone());
 
     rsx!(div { style: "padding:16px; font-family:Arial, sans-serif;",
         style { "{style_content}" }
-        if let Some(screen_id) = selected_screen.with(|s| *s) {
+        if let Some((qa_label, qa_url)) = current_quick_add {
+            div { style: "position:fixed; inset:0; background:rgba(0,0,0,0.3); display:flex; align-items:center; justify-content:center;",
+                form { style: "background:#fff; padding:16px; display:flex; flex-direction:column; gap:8px; min-width:360px;",
+                    onsubmit: move |e| {
+                        e.prevent_default();
+                        let Some((lab, urlv)) = quick_add.with(|q| q.clone()) else {
+                            return;
+                        };
+                        match validate_entry(&lab, &urlv) {
+                            Ok((lab, normalized)) => match already_saved(&normalized) {
+                                Some(msg) => error_msg.set(msg),
+                                None => {
+                                    error_msg.set(String::new());
+                                    db_coroutine.send(DbAction::Insert(lab, normalized, Utc::now().timestamp()));
+                                    quick_add.set(None);
+                                }
+                            },
+                            Err(msg) => error_msg.set(msg),
+                        }
+                    },
+                    b { "Add URL" }
+                    input { placeholder: "Label", value: "{qa_label}",
+                        oninput: move |e| if let Some(q) = quick_add.write().as_mut() { q.0 = e.value() },
+                    }
+                    input { placeholder: "URL", value: "{qa_url}", autofocus: true,
+                        oninput: move |e| if let Some(q) = quick_add.write().as_mut() { q.1 = e.value() },
+                    }
+                    if !current_error.is_empty() {
+                        p { style: "color: #c00; margin:0;", "{current_error}" }
+                    }
+                    div { style: "display:flex; gap:8px; justify-content:flex-end;",
+                        button { r#type: "button", onclick: move |_| quick_add.set(None), "Cancel" }
+                        button { "Add" }
+                    }
+                }
+            }
+        }
+        if let Some(query) = current_launcher {
+            div { style: "position:fixed; inset:0; background:rgba(0,0,0,0.3); display:flex; align-items:flex-start; justify-content:center; padding-top:64px;",
+                form { style: "background:#fff; padding:16px; display:flex; flex-direction:column; gap:8px; min-width:480px;",
+                    onsubmit: move |e| {
+                        e.prevent_default();
+                        if let Some(rec) = launcher_hits.with(|v| v.first().cloned()) {
+                            open_launcher_hit(rec);
+                        }
+                    },
+                    input { r#type: "search", placeholder: "Open a saved URL", value: "{query}", autofocus: true,
+                        oninput: move |e| {
+                            launcher_hits.set(launcher_results(&e.value()));
+                            launcher.set(Some(e.value()));
+                        },
+                        onkeydown: move |e| if e.key() == Key::Escape { launcher.set(None) },
+                    }
+                    for rec in current_launcher_hits.iter().cloned() {
+                        a { href: "#", onclick: move |e| {
+                                e.prevent_default();
+                                open_launcher_hit(rec.clone());
+                            }, "{rec.label} — {rec.url}" }
+                    }
+                    if current_launcher_hits.is_empty() {
+                        p { style: "color:#888; margin:0;", "No match" }
+                    }
+                }
+            }
+        }
+        if current_show_settings {
+            h1 { "Settings" }
+            h2 { "Window" }
+            label { style: "display:block; margin-top:8px;",
+                input { r#type: "checkbox", checked: current_window_settings.close_to_tray,
+                    onchange: move |e| {
+                        let on = e.checked();
+                        match crate::config::update(|c| c.window.close_to_tray = on) {
+                            Ok(()) => {
+                                error_msg.set(String::new());
+                                window_settings.write().close_to_tray = on;
+                            }
+                            Err(e) => error_msg.set(format!("Failed to save settings: {}", e)),
+                        }
+                    },
+                }
+                " Keep running in the tray when the window is closed"
+            }
+            label { style: "display:block; margin-top:8px;",
+                input { r#type: "checkbox", checked: current_window_settings.start_on_login,
+                    onchange: move |e| {
+                        let on = e.checked();
+                        // the login entry first: don't save a setting it does not reflect
+                        let res = crate::autostart::sync(on)
+                            .and_then(|()| crate::config::update(|c| c.window.start_on_login = on));
+                        match res {
+                            Ok(()) => {
+                                error_msg.set(String::new());
+                                window_settings.write().start_on_login = on;
+                            }
+                            Err(e) => error_msg.set(format!("Failed to change start at login: {}", e)),
+                        }
+                    },
+                }
+                " Start at login, in the tray"
+            }
+            h2 { "Keyboard shortcuts" }
+            p { style: "color:#666;",
+                "Global shortcuts work while Rustine runs, even from other applications. Write them like CmdOrCtrl+Alt+R; leave one empty to turn it off."
+            }
+            for action in crate::hotkeys::Action::ALL {
+                label { style: "display:block; margin-top:8px;",
+                    "{action.label()} "
+                    input { value: "{action.binding(&current_hotkey_form)}",
+                        oninput: move |e| *action.binding_mut(&mut hotkey_form.write()) = e.value(),
+                    }
+                }
+                for conflict in current_hotkey_conflicts.iter().filter(|c| c.action == action) {
+                    p { style: "color: #c00; margin:0;", "{conflict.reason}" }
+                }
+            }
+            if !current_error.is_empty() {
+                p { style: "color: #c00; margin-top:8px;", "{current_error}" }
+            }
+            div { style: "margin-top:8px; display:flex; gap:8px;",
+                button { onclick: move |_| show_settings.set(false), "Back" }
+                button { onclick: move |_| {
+                        let form = hotkey_form.with(|f| f.clone());
+                        match crate::hotkeys::rebind(&form) {
+                            Ok(conflicts) if conflicts.is_empty() => {
+                                match crate::config::update(|c| c.hotkeys = form) {
+                                    Ok(()) => {
+                                        error_msg.set(String::new());
+                                        status_msg.set("Keyboard shortcuts saved".to_string());
+                                        hotkey_conflicts.set(Vec::new());
+                                        show_settings.set(false);
+                                    }
+                                    Err(e) => error_msg.set(format!("Failed to save settings: {}", e)),
+                                }
+                            }
+                            Ok(conflicts) => hotkey_conflicts.set(conflicts),
+                            Err(e) => error_msg.set(e.to_string()),
+                        }
+                    },
+                    "Save"
+                }
+            }
+        } else if let Some(screen_id) = selected_screen.with(|s| *s) {
             h1 { "Details" }
             p { "Screen id: {screen_id}" }
+            if let Some(site) = current_details_site {
+                div { style: "margin:8px 0; padding:4px 8px; border-left:4px solid {theme_accent(&site)};",
+                    if let (Some(mime), Some(data)) = (site.preview_mime.clone(), site.preview_data.clone()) {
+                        img { src: "data:{mime};base64,{STANDARD.encode(&data)}", style: "max-width:320px; max-height:180px; display:block;" }
+                    }
+                    for (label, value) in site_detail_rows(&site) {
+                        p { style: "margin:2px 0;", b { "{label}: " } "{value}" }
+                    }
+                }
+            }
+            form { onsubmit: move |e| {
+                    e.prevent_default();
+                    let lab = edit_label.with(|s| s.clone());
+                    let urlv = edit_url.with(|s| s.clone());
+                    match validate_entry(&lab, &urlv) {
+                        Ok((lab, normalized)) => {
+                            error_msg.set(String::new());
+                            let db_coroutine = db_coroutine.clone();
+                            db_coroutine.send(DbAction::Update(screen_id, lab, normalized));
+                        }
+                        Err(msg) => error_msg.set(msg),
+                    }
+                },
+                input { placeholder: "Label", value: "{current_edit_label}", oninput: move |e| edit_label.set(e.value().clone()) }
+                input { placeholder: "URL", value: "{current_edit_url}", oninput: move |e| edit_url.set(e.value().clone()) }
+                button { "Save" }
+            }
+            div { style: "display:flex; gap:4px; align-items:center; margin-top:8px;",
+                "Tags:"
+                for name in current_details_tags.iter().cloned() {
+                    span { style: "background:#eee; border-radius:8px; padding:0 6px;",
+                        "{name} "
+                        button { title: "Remove tag", onclick: move |_| {
+                                db_coroutine.send(DbAction::Untag(screen_id, name.clone()));
+                            }, "×" }
+                    }
+                }
+            }
+            form { onsubmit: move |e| {
+                    e.prevent_default();
+                    let name = tag_input.with(|s| s.clone());
+                    if name.trim().is_empty() {
+                        error_msg.set("The tag cannot be empty".to_string());
+                        return;
+                    }
+                    db_coroutine.send(DbAction::Tag(screen_id, name));
+                },
+                input { placeholder: "New tag", value: "{current_tag_input}", oninput: move |e| tag_input.set(e.value().clone()) }
+                button { "Add tag" }
+            }
+            label { style: "display:block; margin-top:8px;",
+                input { r#type: "checkbox", checked: current_details_no_fetch,
+                    onchange: move |_| db_coroutine.send(DbAction::SetNoFetch(screen_id, !current_details_no_fetch)),
+                }
+                " Don't fetch metadata for this URL"
+            }
+            div { style: "margin-top:8px;",
+                b { "Link checks " }
+                button { onclick: move |_| db_coroutine.send(DbAction::CheckLink(screen_id)), "Check now" }
+                if current_details_links.is_empty() {
+                    p { style: "color:#888;", "Never checked" }
+                }
+                ul {
+                    for check in current_details_links.iter() {
+                        li { style: if check.is_broken() { "color:#b00;" } else { "" },
+                            title: "{check.error.clone().unwrap_or_default()}",
+                            "{link_check_summary(check)}"
+                        }
+                    }
+                }
+            }
+            if !current_error.is_empty() {
+                p { style: "color: #c00; margin-top:8px;", "{current_error}" }
+            }
             button { onclick: move |_| selected_screen.set(None), "Back" }
         } else {
             h1 { "Rustine — reactive list" }
             form { onsubmit: move |e| {
                     e.prevent_default();
-                    let lab = label_input.with(|s| s.clone()).trim().to_string();
-                    let urlv = url_input.with(|s| s.clone()).trim().to_string();
-                    // strict validation: non-empty, valid URL, http(s) scheme, has host
+                    let lab = label_input.with(|s| s.clone());
+                    let urlv = url_input.with(|s| s.clone());
                     error_msg.set(String::new());
-                    if lab.is_empty() {
-                        error_msg.set("The label cannot be empty".to_string());
-                        return;
-                    }
-                    if urlv.is_empty() {
-                        error_msg.set("The URL cannot be empty".to_string());
-                        return;
-                    }
-                    let parsed = match Url::parse(&urlv) {
-                        Ok(u) => u,
-                        Err(_) => {
-                            error_msg.set("Invalid URL".to_string());
+                    let (lab, normalized) = match validate_entry(&lab, &urlv) {
+                        Ok(v) => v,
+                        Err(msg) => {
+                            error_msg.set(msg);
                             return;
                         }
                     };
-                    let scheme = parsed.scheme();
-                    if scheme != "http" && scheme != "https" {
-                        error_msg.set("Only http(s) URLs are supported".to_string());
-                        return;
-                    }
-                    if parsed.host().is_none() {
-                        error_msg.set("The URL must contain a host".to_string());
+                    // refuse duplicates up front, keeping what was typed
+                    if let Some(msg) = already_saved(&normalized) {
+                        error_msg.set(msg);
                         return;
                     }
-                    // normalize URL (e.g. add trailing slash if parsed)
-                    let normalized = Into::<String>::into(parsed);
                     let db_coroutine = db_coroutine.clone();
                     // send insert action with timestamp
                     db_coroutine.send(DbAction::Insert(lab, normalized, Utc::now().timestamp()));
@@ -231,27 +930,162 @@ fn root() -> Element {
                 input { placeholder: "URL", value: "{current_url}", oninput: move |e| url_input.set(e.value().clone()) }
                 button { "Add" }
             }
+            button { style: "margin-top:8px;", onclick: move |_| db_coroutine.send(DbAction::ImportBookmarks), "Import bookmarks…" }
+            button { style: "margin-top:8px; margin-left:8px;", onclick: move |_| db_coroutine.send(DbAction::Export), "Export…" }
+            button { style: "margin-top:8px; margin-left:8px;", onclick: move |_| db_coroutine.send(DbAction::RefreshAllMetadata), "Refresh all metadata" }
+            button { style: "margin-top:8px; margin-left:8px;", onclick: move |_| db_coroutine.send(DbAction::CheckLinks), "Check links" }
+            button { style: "margin-top:8px; margin-left:8px;", onclick: move |_| db_coroutine.send(DbAction::FindDuplicates), "Find duplicates" }
+            button { style: "margin-top:8px; margin-left:8px;", onclick: move |_| {
+                    let config = crate::config::current();
+                    window_settings.set(config.window);
+                    hotkey_form.set(config.hotkeys);
+                    hotkey_conflicts.set(crate::hotkeys::conflicts());
+                    error_msg.set(String::new());
+                    show_settings.set(true);
+                },
+                "Settings…"
+            }
+            if let Some(groups) = current_duplicates {
+                div { style: "margin-top:8px; padding:4px 8px; border:1px solid #ddd;",
+                    b { "Duplicates " }
+                    button { onclick: move |_| duplicates.set(None), "Close" }
+                    for group in groups {
+                        div { style: "margin-top:4px;",
+                            span { style: "color:#666;", "{group.canonical} " }
+                            button { title: "Keep the oldest entry, with the others' favorite, tags and visits",
+                                onclick: move |_| db_coroutine.send(DbAction::MergeDuplicates(group.ids())),
+                                "Merge"
+                            }
+                            ul {
+                                for rec in group.records.iter() {
+                                    li { "{rec.label} — {rec.url}" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if !current_status_msg.is_empty() {
+                p { style: "margin-top:8px;", "{current_status_msg}" }
+            }
             if !current_error.is_empty() {
                 p { style: "color: #c00; margin-top:8px;", "{current_error}" }
             }
-            ul {
-                for rec in current_urls.iter().cloned() {
-                    li { style: "display:flex; gap:8px; align-items:center;",
-                        { if let Some(data) = rec.icon_data.clone() {
-                            let mime = rec.icon_mime.clone().unwrap_or_else(|| "image/png".to_string());
-                            let b64 = STANDARD.encode(&data);
-                            let src = format!("data:{};base64,{}", mime, b64);
-                            rsx!(img { src: "{src}", width: "16", height: "16", style: "border-radius:2px;" })
-                        } else { rsx!() } }
-                        a { href: "#", onclick: move |e| {
-                                e.prevent_default();
-                                let u = rec.url.clone();
-                                if let Err(err) = crate::webview::open_url(u) {
-                                    error_msg.set(format!("Error opening URL: {}", err));
+            input { r#type: "search", placeholder: "Search", value: "{current_query}",
+                style: "display:block; margin-top:8px; width:100%;",
+                oninput: move |e| {
+                    search_query.set(e.value().clone());
+                    db_coroutine.send(DbAction::Load);
+                }
+            }
+            label { style: "display:block; margin-top:8px;",
+                input { r#type: "checkbox", checked: current_favorites_only, onchange: move |e| {
+                        favorites_only.set(e.checked());
+                        let db_coroutine = db_coroutine.clone();
+                        db_coroutine.send(DbAction::Load);
+                    } }
+                " Favorites only"
+            }
+            div { style: "display:flex; gap:16px; margin-top:8px;",
+                nav { style: "min-width:140px;",
+                    ul {
+                        li {
+                            a { href: "#", style: if current_tag_filter.is_none() && !current_broken_only { "font-weight:bold;" } else { "" },
+                                onclick: move |e| {
+                                    e.prevent_default();
+                                    tag_filter.set(None);
+                                    broken_only.set(false);
+                                    db_coroutine.send(DbAction::Load);
+                                }, "All" }
+                        }
+                        li {
+                            a { href: "#", style: if current_broken_only { "font-weight:bold;" } else { "" },
+                                onclick: move |e| {
+                                    e.prevent_default();
+                                    tag_filter.set(None);
+                                    broken_only.set(true);
+                                    db_coroutine.send(DbAction::Load);
+                                }, "Broken links" }
+                        }
+                        for tag in current_tags.iter().cloned() {
+                            li {
+                                a { href: "#",
+                                    style: if current_tag_filter.as_deref() == Some(tag.name.as_str()) { "font-weight:bold;" } else { "" },
+                                    onclick: move |e| {
+                                        e.prevent_default();
+                                        tag_filter.set(Some(tag.name.clone()));
+                                        broken_only.set(false);
+                                        db_coroutine.send(DbAction::Load);
+                                    }, "{tag.name} ({tag.url_count})" }
+                            }
+                        }
+                    }
+                }
+                ul {
+                    for rec in current_urls.iter().cloned() {
+                        li { style: "display:flex; gap:8px; align-items:center;",
+                            { if let Some(data) = rec.icon_data.clone() {
+                                let mime = rec.icon_mime.clone().unwrap_or_else(|| "image/png".to_string());
+                                let b64 = STANDARD.encode(&data);
+                                let src = format!("data:{};base64,{}", mime, b64);
+                                rsx!(img { src: "{src}", width: "16", height: "16", style: "border-radius:2px;" })
+                            } else { rsx!() } }
+                            button { title: if rec.is_favorite { "Remove from favorites" } else { "Add to favorites" },
+                                onclick: move |_| {
+                                    let db_coroutine = db_coroutine.clone();
+                                    db_coroutine.send(DbAction::SetFavorite(rec.id, !rec.is_favorite));
+                                },
+                                if rec.is_favorite { "★" } else { "☆" }
+                            }
+                            a { href: "#", onclick: move |e| {
+                                    e.prevent_default();
+                                    let u = rec.url.clone();
+                                    if let Err(err) = crate::webview::open_saved_url(rec.id, u) {
+                                        error_msg.set(format!("Error opening URL: {}", err));
+                                    }
+                                }, "{rec.label} — {rec.url}" }
+                            match current_meta_jobs.get(&rec.id) {
+                                Some(MetaJob::Fetching) => rsx!(span { style: "color:#888; font-size:0.85em;", "fetching…" }),
+                                Some(MetaJob::Failed(err)) => rsx!(span { style: "color:#b00; font-size:0.85em;", title: "{err}", "failed" }),
+                                None => rsx!(),
+                            }
+                            if rec.link_broken {
+                                span { style: "background:#b00; color:#fff; border-radius:8px; padding:0 6px; font-size:0.85em;",
+                                    title: "The last link check failed", "broken" }
+                            }
+                            if let Some(to) = rec.moved_to.clone() {
+                                button { title: "Redirects to {to}",
+                                    onclick: move |_| db_coroutine.send(DbAction::ApplyRedirect(rec.id, to.clone())),
+                                    "Update URL"
                                 }
-                            }, "{rec.label} — {rec.url}" }
-                        button { onclick: move |_| on_delete(rec.id), "Delete" }
-                        button { onclick: move |_| on_details(rec.id), "Details" }
+                            }
+                            if let Some(snippet) = current_snippets.get(&rec.id) {
+                                span { style: "color:#666; font-size:0.9em;",
+                                    for (text, highlighted) in crate::db::snippet_segments(snippet) {
+                                        if highlighted {
+                                            mark { "{text}" }
+                                        } else {
+                                            "{text}"
+                                        }
+                                    }
+                                }
+                            }
+                            for name in rec.tags.iter().cloned() {
+                                a { href: "#", style: "background:#eee; border-radius:8px; padding:0 6px; font-size:0.85em;",
+                                    onclick: move |e| {
+                                        e.prevent_default();
+                                        tag_filter.set(Some(name.clone()));
+                                        db_coroutine.send(DbAction::Load);
+                                    }, "{name}" }
+                            }
+                            button { onclick: move |_| on_delete(rec.id), "Delete" }
+                            button { onclick: move |_| on_details(rec.id), "Details" }
+                            button { title: "Fetch this site's name and icons again",
+                                disabled: matches!(current_meta_jobs.get(&rec.id), Some(MetaJob::Fetching)),
+                                onclick: move |_| db_coroutine.send(DbAction::RefreshMetadata(rec.id)),
+                                "Refresh"
+                            }
+                        }
                     }
                 }
             }
@@ -259,13 +1093,436 @@ fn root() -> Element {
     })
 }
 
+/// Site metadata for the origin of `url`, if any was fetched.
+fn load_site_meta(url: &str) -> Option<crate::db::SiteMeta> {
+    let db = crate::db::get_global()?;
+    let origin = crate::urlnorm::origin(url)?;
+    db.get_site_meta_by_origin(&origin).ok().flatten()
+}
+
+/// Most recent link checks of row `id` for the details screen.
+fn load_link_history(id: i64) -> Vec<crate::db::LinkCheck> {
+    crate::db::get_global()
+        .and_then(|db| db.link_history(id, 10).ok())
+        .unwrap_or_default()
+}
+
+/// One line of link-check history: when, outcome, where it ended up, how long.
+fn link_check_summary(check: &crate::db::LinkCheck) -> String {
+    let when = chrono::DateTime::from_timestamp(check.checked_at, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default();
+    let outcome = match (check.status, &check.error_kind) {
+        (Some(status), _) => status.to_string(),
+        (None, Some(kind)) => format!("{} error", kind),
+        (None, None) => "no response".to_string(),
+    };
+    match &check.final_url {
+        Some(to) => format!("{} — {} → {} ({} ms)", when, outcome, to, check.latency_ms),
+        None => format!("{} — {} ({} ms)", when, outcome, check.latency_ms),
+    }
+}
+
+/// Labelled, non-empty metadata fields for the details screen.
+fn site_detail_rows(site: &crate::db::SiteMeta) -> Vec<(&'static str, String)> {
+    let page = &site.page;
+    [
+        ("Site", &site.site_name),
+        ("Title", &page.title),
+        ("Description", &site.description),
+        ("Application", &page.application_name),
+        ("OpenGraph site", &page.og_site_name),
+        ("OpenGraph title", &page.og_title),
+        ("OpenGraph description", &page.og_description),
+        ("OpenGraph image", &page.og_image),
+        ("Twitter title", &page.twitter_title),
+        ("Twitter description", &page.twitter_description),
+        ("Twitter image", &page.twitter_image),
+        ("Theme color", &page.theme_color),
+        ("Manifest", &site.manifest_url),
+    ]
+    .into_iter()
+    .filter_map(|(label, value)| Some((label, value.clone()?)))
+    .collect()
+}
+
+/// The site's `theme-color` for use in inline CSS, or a neutral grey when it
+/// is missing or contains anything beyond a plain color value.
+fn theme_accent(site: &crate::db::SiteMeta) -> String {
+    site.page
+        .theme_color
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| {
+            !c.is_empty()
+                && c.chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || "#(),.% ".contains(ch))
+        })
+        .unwrap_or("#ddd")
+        .to_string()
+}
+
+/// Saved URLs matching the launcher query, best first; the most used ones
+/// while it is empty.
+fn launcher_results(query: &str) -> Vec<crate::db::UrlRecord> {
+    const LIMIT: i64 = 8;
+    let Some(db) = crate::db::get_global() else {
+        return Vec::new();
+    };
+    let res = if query.trim().is_empty() {
+        db.list_frecent(LIMIT)
+    } else {
+        db.search(query, LIMIT)
+            .map(|hits| hits.into_iter().map(|hit| hit.record).collect())
+    };
+    res.unwrap_or_else(|e| {
+        eprintln!("launcher search failed: {}", e);
+        Vec::new()
+    })
+}
+
+/// Why `url` can't be added when it is already saved under any spelling
+/// (see `DbHandle::duplicates_of`).
+fn already_saved(url: &str) -> Option<String> {
+    let existing = crate::db::get_global()?.duplicates_of(url).ok()?;
+    let first = existing.first()?;
+    Some(format!(
+        "Already saved as “{}” ({})",
+        first.label, first.url
+    ))
+}
+
+/// The http(s) URL on the clipboard, if there is one.
+fn clipboard_url() -> Option<String> {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .ok()?;
+    crate::urlnorm::http_url_in(&text)
+}
+
+/// Strict validation shared by the add and edit forms: non-empty label and URL,
+/// valid http(s) URL with a host. Returns the trimmed label and normalized URL.
+fn validate_entry(label: &str, url: &str) -> Result<(String, String), String> {
+    let lab = label.trim();
+    let urlv = url.trim();
+    if lab.is_empty() {
+        return Err("The label cannot be empty".to_string());
+    }
+    if urlv.is_empty() {
+        return Err("The URL cannot be empty".to_string());
+    }
+    let parsed = Url::parse(urlv).map_err(|_| "Invalid URL".to_string())?;
+    let scheme = parsed.scheme();
+    if scheme != "http" && scheme != "https" {
+        return Err("Only http(s) URLs are supported".to_string());
+    }
+    if parsed.host().is_none() {
+        return Err("The URL must contain a host".to_string());
+    }
+    // normalize URL (e.g. add trailing slash if parsed)
+    Ok((lab.to_string(), Into::<String>::into(parsed)))
+}
+
+/// State of the metadata fetch started when a row is added; rows without an
+/// entry are done (or predate the job).
+#[derive(Debug, Clone, PartialEq)]
+enum MetaJob {
+    Fetching,
+    Failed(String),
+}
+
+/// Filters applied to the main list.
+struct ListFilter {
+    favorites_only: bool,
+    broken_only: bool,
+    tag: Option<String>,
+    query: String,
+}
+
+impl ListFilter {
+    fn keeps(&self, rec: &crate::db::UrlRecord) -> bool {
+        (!self.favorites_only || rec.is_favorite)
+            && (!self.broken_only || rec.link_broken)
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| rec.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+    }
+}
+
+/// Load the main list, honouring the search box, tag, broken-links and
+/// favorites-only filters.
+/// Search hits also yield their highlighted snippet, keyed by row id.
+fn load_list(
+    db: &crate::db::DbHandle,
+    filter: &ListFilter,
+) -> anyhow::Result<(Vec<crate::db::UrlRecord>, HashMap<i64, String>)> {
+    if !filter.query.trim().is_empty() {
+        let mut list = Vec::new();
+        let mut found = HashMap::new();
+        for hit in db.search(&filter.query, 100)? {
+            if !filter.keeps(&hit.record) {
+                continue;
+            }
+            found.insert(hit.record.id, hit.snippet);
+            list.push(hit.record);
+        }
+        return Ok((list, found));
+    }
+    let list = if let Some(tag) = &filter.tag {
+        db.list_by_tag(tag)?
+    } else if filter.broken_only {
+        db.list_broken_links()?
+    } else if filter.favorites_only {
+        db.list_favorites()?
+    } else {
+        db.list_frecent(100)?
+    };
+    Ok((
+        list.into_iter().filter(|r| filter.keeps(r)).collect(),
+        HashMap::new(),
+    ))
+}
+
 // Helper enum for DB actions
 enum DbAction {
     Load,
     Delete(i64),
     Insert(String, String, i64),
+    Update(i64, String, String),
+    SetFavorite(i64, bool),
+    SetNoFetch(i64, bool),
+    Tag(i64, String),
+    Untag(i64, String),
+    ImportBookmarks,
+    Export,
+    RefreshMetadata(i64),
+    RefreshAllMetadata,
+    CheckLink(i64),
+    CheckLinks,
+    ApplyRedirect(i64, String),
+    FindDuplicates,
+    MergeDuplicates(Vec<i64>),
 }
 
+/// Root of the resident window: hidden, it lives as long as the process and
+/// owns the tray side of the UI (menu clicks, menu refreshes, `TrayEvent`s),
+/// so closing the main window only drops the main window's UI.
 pub fn app() -> Element {
-    root()
+    // a main window was requested and is not up yet; only read and written
+    // outside rendering, so it never re-renders this component
+    let opening = use_signal(|| false);
+    let start_minimized = use_hook(|| try_consume_context::<StartMinimized>().is_some_and(|s| s.0));
+
+    // The desktop event loop owns the menu event handler: hand clicks to the
+    // tray. Tray menus are muda menus and the runtime sets its menubar handler
+    // first, so their clicks arrive as muda events, not tray menu events.
+    dioxus_desktop::use_muda_event_handler(|event| {
+        if let Err(e) = crate::tray::dispatch_menu_event(event.id.as_ref()) {
+            eprintln!("tray menu event failed: {}", e);
+        }
+    });
+
+    // Left-clicking the tray icon makes the desktop runtime show every window,
+    // this one included: hide it again once that happened, and show the main
+    // window instead.
+    dioxus_desktop::use_tray_icon_event_handler(|event| {
+        use dioxus_desktop::trayicon::{MouseButton, MouseButtonState, TrayIconEvent};
+        if let TrayIconEvent::Click {
+            button: MouseButton::Left,
+            button_state,
+            ..
+        } = event
+        {
+            spawn(async {
+                dioxus_desktop::window().set_visible(false);
+            });
+            if *button_state == MouseButtonState::Up {
+                let _ = crate::tray::send_event(TrayEvent::Show);
+            }
+        }
+    });
+
+    // tray -> UI: forward TrayEvent into a dioxus future so UI/main-thread
+    // code (not a background thread) performs actions like opening URLs.
+    // Built once: a second forwarder would split the events between the two.
+    let tray_rx = use_hook(|| {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<TrayEvent>();
+        // Background thread: read the crossbeam receiver and forward into the async channel.
+        if let Some(tray_rx) = crate::tray::get_receiver() {
+            std::thread::spawn(move || {
+                while let Ok(ev) = tray_rx.recv() {
+                    let _ = tx.unbounded_send(ev);
+                }
+            });
+        }
+        Rc::new(RefCell::new(Some(rx)))
+    });
+
+    use_future(move || {
+        let rx = tray_rx.borrow_mut().take();
+        async move {
+            if !start_minimized {
+                show_main_window(opening, None);
+            }
+            let Some(mut rx) = rx else {
+                return;
+            };
+            while let Some(ev) = rx.next().await {
+                match ev {
+                    TrayEvent::OpenUrl(id) => {
+                        let Some(db) = crate::db::get_global() else {
+                            report("Base de données non disponible".to_string());
+                            continue;
+                        };
+                        match db.get_by_id(id) {
+                            Ok(Some(rec)) => {
+                                if let Err(e) = crate::webview::open_saved_url(rec.id, rec.url) {
+                                    report(format!("Erreur ouverture URL (tray): {}", e));
+                                }
+                            }
+                            Ok(None) => report(format!("URL introuvable (id={})", id)),
+                            Err(e) => report(format!("Erreur DB (tray): {}", e)),
+                        }
+                    }
+                    TrayEvent::Show => show_main_window(opening, None),
+                    TrayEvent::Add => {
+                        let url = clipboard_url().unwrap_or_default();
+                        let label = crate::urlnorm::label_for(&url).unwrap_or_default();
+                        show_main_window(opening, Some(MainRequest::QuickAdd(label, url)));
+                    }
+                    TrayEvent::Launcher => show_main_window(opening, Some(MainRequest::Launcher)),
+                    TrayEvent::AddClipboard => add_clipboard(opening),
+                    TrayEvent::Quit => quit(),
+                }
+            }
+        }
+    });
+
+    // DB changes -> tray: rebuild the tray menus on this (main) thread once per
+    // burst of events, whether or not the main window is open.
+    use_future(move || async move {
+        let Some(db) = crate::db::get_global() else {
+            return;
+        };
+        let mut changes = match db.subscribe() {
+            Ok(sub) => sub.into_stream(),
+            Err(e) => {
+                eprintln!("DB subscription failed: {}", e);
+                return;
+            }
+        };
+        while changes.next().await.is_some() {
+            while let Some(Some(_)) = changes.next().now_or_never() {}
+            if let Err(e) = crate::tray::refresh_tray(&db) {
+                eprintln!("tray refresh failed: {}", e);
+            }
+        }
+    });
+
+    rsx!()
+}
+
+/// Bring the main window to the front, restoring it if minimized, or open a
+/// new one if it was closed, then hand it `request`.
+fn show_main_window(mut opening: Signal<bool>, request: Option<MainRequest>) {
+    if let Some((window, tx)) = main_window() {
+        window.set_visible(true);
+        window.set_minimized(false);
+        window.set_focus();
+        if let Some(request) = request {
+            let _ = tx.unbounded_send(request);
+        }
+        return;
+    }
+    if *opening.peek() {
+        return;
+    }
+    opening.set(true);
+    let dom = VirtualDom::new(root).with_root_context(InitialRequest(request));
+    let config = dioxus_desktop::Config::new()
+        .with_window(WindowBuilder::new().with_title("Rustine"))
+        .with_close_behaviour(WindowCloseBehaviour::WindowCloses);
+    let pending = dioxus_desktop::window().new_window(dom, config);
+    spawn(async move {
+        let _ = pending.try_resolve().await;
+        opening.set(false);
+    });
+}
+
+/// Global "add the clipboard URL" shortcut: save the URL without opening the
+/// main window, or open an empty quick-add dialog if there is no URL to save.
+fn add_clipboard(opening: Signal<bool>) {
+    let Some(url) = clipboard_url() else {
+        show_main_window(
+            opening,
+            Some(MainRequest::QuickAdd(String::new(), String::new())),
+        );
+        return;
+    };
+    let Some(db) = crate::db::get_global() else {
+        report("Base de données non disponible".to_string());
+        return;
+    };
+    if let Some(msg) = already_saved(&url) {
+        announce(msg);
+        return;
+    }
+    let label = crate::urlnorm::label_for(&url).unwrap_or_else(|| url.clone());
+    let (label, url) = match validate_entry(&label, &url) {
+        Ok(entry) => entry,
+        Err(msg) => return report(msg),
+    };
+    match db.insert_url(&label, &url, Utc::now().timestamp()) {
+        Ok(_) => {
+            announce(format!("Added “{}” from the clipboard", label));
+            std::thread::spawn(move || {
+                let reason = crate::metadata::FetchReason::Added;
+                if let Err(e) = crate::metadata::fetch_and_store(&db, &url, reason) {
+                    eprintln!("metadata fetch for {} failed: {}", url, e);
+                }
+            });
+        }
+        Err(e) => report(format!("Erreur DB: {}", e)),
+    }
+}
+
+/// Show a tray error in the main window, or log it while there is none.
+fn report(msg: String) {
+    match main_window() {
+        Some((_, tx)) => {
+            let _ = tx.unbounded_send(MainRequest::Error(msg));
+        }
+        None => eprintln!("{}", msg),
+    }
+}
+
+/// Show the outcome of a background action in the main window, or log it
+/// while there is none.
+fn announce(msg: String) {
+    match main_window() {
+        Some((_, tx)) => {
+            let _ = tx.unbounded_send(MainRequest::Status(msg));
+        }
+        None => println!("{}", msg),
+    }
+}
+
+/// Tray "Quit": remove the tray icon, close the webview window, stop the DB
+/// actor (flushing what it wrote), then close both windows, which ends the app.
+fn quit() {
+    crate::tray::shutdown_tray();
+    if let Err(e) = crate::webview::shutdown() {
+        eprintln!("webview shutdown failed: {}", e);
+    }
+    if let Some(db) = crate::db::get_global() {
+        if let Err(e) = db.shutdown() {
+            eprintln!("DB shutdown failed: {}", e);
+        }
+    }
+    let main = main_window().map(|(window, _)| window);
+    for window in main.into_iter().chain([dioxus_desktop::window()]) {
+        window.set_close_behavior(WindowCloseBehaviour::WindowCloses);
+        window.close();
+    }
 }
@@ -0,0 +1,236 @@
+//! URL normalization helpers shared by the DB actor and the metadata fetchers.
+
+use url::form_urlencoded;
+use url::Url;
+
+/// Query parameters that only record where a click came from; `utm_*` is
+/// matched by prefix.
+const TRACKING_PARAMS: [&str; 12] = [
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "mc_cid", "mc_eid",
+    "igshid", "_ga", "_gl",
+];
+
+/// Normalized origin of `url` as `scheme://host:port`, the key used for
+/// `site_meta.origin` and `urls.origin`.
+///
+/// The scheme and host are lowercased, internationalized domain names are
+/// converted to punycode and the port is always the effective one (explicit or
+/// the scheme default). Returns `None` for URLs without a host (e.g. `data:`).
+pub fn origin(url: &str) -> Option<String> {
+    let parsed = Url::parse(url.trim()).ok()?;
+    let host = parsed.host_str()?;
+    let port = parsed.port_or_known_default()?;
+    Some(format!(
+        "{}://{}:{}",
+        parsed.scheme(),
+        host.to_ascii_lowercase(),
+        port
+    ))
+}
+
+/// Canonical form of an http(s) `url`, the key used to spot duplicates in
+/// `urls.canonical`. Not meant to be opened: `http` and `https` share a key.
+///
+/// On top of what `url` already normalizes (scheme and host case, default
+/// ports, IDN), the host loses a trailing dot, the path its trailing slash,
+/// tracking parameters are dropped and the rest sorted by name. Fragments are
+/// dropped unless they look like a client-side route (`#/…`, `#!…`). Returns
+/// `None` for other schemes and unparsable input.
+pub fn canonical(url: &str) -> Option<String> {
+    let parsed = Url::parse(url.trim()).ok()?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return None;
+    }
+    let host = parsed.host_str()?.trim_end_matches('.');
+    let mut key = format!("https://{}", host.to_ascii_lowercase());
+    if let Some(port) = parsed.port() {
+        key.push_str(&format!(":{}", port));
+    }
+    key.push_str(parsed.path().trim_end_matches('/'));
+
+    let mut pairs: Vec<_> = parsed
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .collect();
+    // stable: repeated names keep their relative order
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+    if !pairs.is_empty() {
+        key.push('?');
+        key.push_str(
+            &form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish(),
+        );
+    }
+    if let Some(route) = parsed
+        .fragment()
+        .filter(|f| f.starts_with('/') || f.starts_with('!'))
+    {
+        key.push('#');
+        key.push_str(route);
+    }
+    Some(key)
+}
+
+/// First http(s) URL with a host found in free `text` (e.g. the clipboard),
+/// with the quotes or angle brackets around it stripped.
+pub fn http_url_in(text: &str) -> Option<String> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c| matches!(c, '<' | '>' | '"' | '\'' | '(' | ')')))
+        .filter_map(|word| Url::parse(word).ok())
+        .find(|u| matches!(u.scheme(), "http" | "https") && u.host_str().is_some())
+        .map(String::from)
+}
+
+/// Default label for a new entry: the host of `url` without `www.`.
+pub fn label_for(url: &str) -> Option<String> {
+    let parsed = Url::parse(url.trim()).ok()?;
+    let host = parsed.host_str()?;
+    Some(host.strip_prefix("www.").unwrap_or(host).to_string())
+}
+
+fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_default_ports() {
+        assert_eq!(
+            origin("https://example.com").as_deref(),
+            Some("https://example.com:443")
+        );
+        assert_eq!(
+            origin("http://example.com").as_deref(),
+            Some("http://example.com:80")
+        );
+    }
+
+    #[test]
+    fn test_origin_explicit_ports() {
+        assert_eq!(
+            origin("http://localhost:8080/app").as_deref(),
+            Some("http://localhost:8080")
+        );
+        // an explicit default port is the same origin as an implicit one
+        assert_eq!(
+            origin("https://example.com:443/").as_deref(),
+            Some("https://example.com:443")
+        );
+    }
+
+    #[test]
+    fn test_origin_ignores_path_query_and_fragment() {
+        assert_eq!(
+            origin("https://example.com/a/b/?q=1#top").as_deref(),
+            Some("https://example.com:443")
+        );
+        assert_eq!(
+            origin("https://example.com/"),
+            origin("https://example.com")
+        );
+    }
+
+    #[test]
+    fn test_origin_case_and_idn() {
+        assert_eq!(
+            origin("HTTPS://Example.COM/Path").as_deref(),
+            Some("https://example.com:443")
+        );
+        assert_eq!(
+            origin("https://bücher.example/").as_deref(),
+            Some("https://xn--bcher-kva.example:443")
+        );
+    }
+
+    #[test]
+    fn test_origin_without_host() {
+        assert_eq!(origin("data:text/plain,hello"), None);
+        assert_eq!(origin("not a url"), None);
+    }
+
+    #[test]
+    fn test_canonical_folds_scheme_case_port_and_slash() {
+        let key = canonical("https://example.com/docs").unwrap();
+        for same in [
+            "http://example.com/docs",
+            "HTTPS://Example.COM/docs/",
+            "https://example.com:443/docs",
+            "http://example.com:80/docs",
+            "https://example.com./docs",
+        ] {
+            assert_eq!(canonical(same).as_deref(), Some(key.as_str()), "{}", same);
+        }
+        assert_eq!(
+            canonical("https://example.com/").as_deref(),
+            Some("https://example.com")
+        );
+        // path case and explicit non-default ports still matter
+        assert_ne!(canonical("https://example.com/Docs").unwrap(), key);
+        assert_ne!(canonical("https://example.com:8443/docs").unwrap(), key);
+    }
+
+    #[test]
+    fn test_canonical_strips_tracking_and_sorts_query() {
+        assert_eq!(
+            canonical("https://example.com/a?utm_source=x&b=2&UTM_Medium=y&fbclid=z&a=1")
+                .as_deref(),
+            Some("https://example.com/a?a=1&b=2")
+        );
+        assert_eq!(
+            canonical("https://example.com/a?utm_campaign=spring").as_deref(),
+            Some("https://example.com/a")
+        );
+        // repeated names keep their order
+        assert_eq!(
+            canonical("https://example.com/?tag=b&tag=a").as_deref(),
+            Some("https://example.com?tag=b&tag=a")
+        );
+    }
+
+    #[test]
+    fn test_canonical_fragments() {
+        assert_eq!(
+            canonical("https://example.com/page#section-2"),
+            canonical("https://example.com/page")
+        );
+        assert_eq!(
+            canonical("https://app.example/#/inbox").as_deref(),
+            Some("https://app.example#/inbox")
+        );
+        assert_eq!(
+            canonical("https://app.example/#!/inbox").as_deref(),
+            Some("https://app.example#!/inbox")
+        );
+    }
+
+    #[test]
+    fn test_canonical_other_schemes() {
+        assert_eq!(canonical("ftp://example.com/file"), None);
+        assert_eq!(canonical("data:text/plain,hello"), None);
+        assert_eq!(canonical("not a url"), None);
+    }
+
+    #[test]
+    fn test_http_url_in_text() {
+        assert_eq!(
+            http_url_in("  see <https://Example.com/a?b=1> for details\n").as_deref(),
+            Some("https://example.com/a?b=1")
+        );
+        assert_eq!(
+            http_url_in("\"http://docs.rs\"").as_deref(),
+            Some("http://docs.rs/")
+        );
+        assert_eq!(http_url_in("mailto:me@example.com ftp://x.org"), None);
+        assert_eq!(http_url_in("just words"), None);
+        assert_eq!(
+            label_for("https://www.rust-lang.org/learn").as_deref(),
+            Some("rust-lang.org")
+        );
+        assert_eq!(label_for("data:text/plain,x"), None);
+    }
+}
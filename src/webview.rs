@@ -2,8 +2,8 @@ use crate::db;
 use anyhow::Result;
 use crossbeam_channel::{unbounded, Sender};
 use once_cell::sync::OnceCell;
-use std::sync::Arc;
-use std::thread;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
 use std::time::{SystemTime, UNIX_EPOCH};
 use url::Url;
 
@@ -11,9 +11,15 @@ use url::Url;
 enum UserEvent {
     Navigate(String),
     Favicon(Vec<u8>),
+    /// Leave the event loop, dropping the webview and its window.
+    Shutdown,
 }
 
 static WEBVIEW_TX: OnceCell<Sender<UserEvent>> = OnceCell::new();
+static WEBVIEW_THREAD: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// Icon size requested for the webview window (taskbar/title bar).
+const WINDOW_ICON_PX: u32 = 64;
 
 fn start_manager() -> Sender<UserEvent> {
     if let Some(tx) = WEBVIEW_TX.get() {
@@ -23,10 +29,12 @@ fn start_manager() -> Sender<UserEvent> {
     let (tx, rx) = unbounded::<UserEvent>();
 
     // Spawn the thread that runs the tao event loop and owns the webview.
-    thread::spawn(move || {
+    let manager = thread::spawn(move || {
+        use tao::platform::run_return::EventLoopExtRunReturn;
+
         // Build event loop (allow any_thread on Windows).
         #[cfg(target_os = "windows")]
-        let event_loop: tao::event_loop::EventLoop<UserEvent> = {
+        let mut event_loop: tao::event_loop::EventLoop<UserEvent> = {
             use tao::event_loop::EventLoopBuilder;
             use tao::platform::windows::EventLoopBuilderExtWindows;
             EventLoopBuilder::with_user_event()
@@ -35,7 +43,7 @@ fn start_manager() -> Sender<UserEvent> {
         };
 
         #[cfg(not(target_os = "windows"))]
-        let event_loop: tao::event_loop::EventLoop<UserEvent> = {
+        let mut event_loop: tao::event_loop::EventLoop<UserEvent> = {
             use tao::event_loop::EventLoopBuilder;
             EventLoopBuilder::with_user_event().build()
         };
@@ -56,6 +64,7 @@ fn start_manager() -> Sender<UserEvent> {
         let proxy_for_channel = proxy.clone();
         thread::spawn(move || {
             while let Ok(ev) = rx.recv() {
+                let last = matches!(ev, UserEvent::Shutdown);
                 if let Err(e) = proxy_for_channel.send_event(ev.clone()) {
                     match ev {
                         UserEvent::Navigate(ref u) => {
@@ -64,8 +73,14 @@ fn start_manager() -> Sender<UserEvent> {
                         UserEvent::Favicon(_) => {
                             eprintln!("webview proxy send_event failed for favicon: {}", e)
                         }
+                        UserEvent::Shutdown => {
+                            eprintln!("webview proxy send_event failed for shutdown: {}", e)
+                        }
                     }
                 }
+                if last {
+                    break;
+                }
             }
         });
 
@@ -75,8 +90,12 @@ fn start_manager() -> Sender<UserEvent> {
         // Move the proxy into the run closure so we can clone it for fetch threads
         let proxy_for_run = proxy.clone();
 
-        event_loop.run(move |event, _, control_flow| {
-            *control_flow = tao::event_loop::ControlFlow::Wait;
+        // `run_return` rather than `run`: the latter exits the whole process
+        // when the loop ends, this one only ends the thread.
+        event_loop.run_return(move |event, _, control_flow| {
+            if *control_flow != tao::event_loop::ControlFlow::Exit {
+                *control_flow = tao::event_loop::ControlFlow::Wait;
+            }
             match event {
                 tao::event::Event::UserEvent(user_ev) => match user_ev {
                     UserEvent::Navigate(url) => {
@@ -89,142 +108,32 @@ fn start_manager() -> Sender<UserEvent> {
                             let _ = window.set_focus();
                             // spawn a background fetch for favicon for this url
                             let proxy_clone = proxy_for_run.clone();
-                            let _window_clone = window.clone();
                             thread::spawn(move || {
-                                // Try /favicon.ico first
-                                if let Ok(parsed) = Url::parse(&url) {
-                                    if let Some(host) = parsed.host_str() {
-                                        let scheme = parsed.scheme();
-                                        let port = parsed.port_or_known_default();
-                                        let origin = if let Some(p) = port { format!("{}://{}:{}", scheme, host, p) } else { format!("{}://{}", scheme, host) };
-                                        let fav_url = format!("{}/favicon.ico", origin.trim_end_matches('/'));
-                                        if let Ok(resp) = reqwest::blocking::get(&fav_url) {
-                                            if resp.status().is_success() {
-                                                if let Ok(bytes) = resp.bytes() {
-                                                                let vec = bytes.to_vec();
-                                                                // Persist site metadata and icon (best-effort)
-                                                                if let Some(dbh) = db::get_global() {
-                                                                    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).ok();
-                                                                    let _ = dbh.upsert_site_meta(&origin, None, None, None, now);
-                                                                    if let Ok(Some(site)) = dbh.get_site_meta_by_origin(&origin) {
-                                                                        let _ = dbh.insert_icon(site.id, &fav_url, None, None, None, vec.clone(), now);
-                                                                    }
-                                                                }
-                                                                let _ = proxy_clone.send_event(UserEvent::Favicon(vec));
-                                                    return;
-                                                }
-                                            }
-                                        }
-
-                                        // If /favicon.ico failed, fetch page HTML and try <link rel> icons
-                                        if let Ok(page_resp) = reqwest::blocking::get(&url) {
-                                            if page_resp.status().is_success() {
-                                                if let Ok(text) = page_resp.text() {
-                                                            use scraper::{Html, Selector};
-                                                            let doc = Html::parse_document(&text);
-
-                                                            // Try to find a web manifest first: <link rel="manifest" href="...">
-                                                            let manifest_sel = Selector::parse("link[rel]").unwrap();
-                                                            let mut manifest_found: Option<String> = None;
-                                                            for element in doc.select(&manifest_sel) {
-                                                                if let Some(rel) = element.value().attr("rel") {
-                                                                    if rel.to_lowercase().contains("manifest") {
-                                                                        if let Some(href) = element.value().attr("href") {
-                                                                            if let Ok(manifest_url) = parsed.join(href) {
-                                                                                manifest_found = Some(manifest_url.to_string());
-                                                                                break;
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-
-                                                            if let Some(manifest_url_str) = manifest_found {
-                                                                if let Ok(manifest_resp) = reqwest::blocking::get(&manifest_url_str) {
-                                                                    if manifest_resp.status().is_success() {
-                                                                        if let Ok(manifest_text) = manifest_resp.text() {
-                                                                            // parse manifest json
-                                                                            if let Ok(man) = serde_json::from_str::<serde_json::Value>(&manifest_text) {
-                                                                                let name = man.get("name").and_then(|v| v.as_str()).map(|s| s.to_string())
-                                                                                    .or_else(|| man.get("short_name").and_then(|v| v.as_str()).map(|s| s.to_string()));
-                                                                                if let Some(dbh) = db::get_global() {
-                                                                                    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).ok();
-                                                                                    let _ = dbh.upsert_site_meta(&origin, name.as_deref(), None, Some(&manifest_url_str), now);
-                                                                                }
-
-                                                                                if let Some(icons) = man.get("icons").and_then(|v| v.as_array()) {
-                                                                                    for icon_entry in icons.iter() {
-                                                                                        if let Some(src) = icon_entry.get("src").and_then(|v| v.as_str()) {
-                                                                                            if let Ok(icon_url) = parsed.join(src) {
-                                                                                                if let Ok(icon_resp) = reqwest::blocking::get(icon_url.as_str()) {
-                                                                                                    if icon_resp.status().is_success() {
-                                                                                                        let mime = icon_resp.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(|s| s.to_string());
-                                                                                                        if let Ok(bytes) = icon_resp.bytes() {
-                                                                                                            let vec = bytes.to_vec();
-                                                                                                            if let Some(dbh) = db::get_global() {
-                                                                                                                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).ok();
-                                                                                                                let _ = dbh.upsert_site_meta(&origin, name.as_deref(), None, Some(&manifest_url_str), now);
-                                                                                                                if let Ok(Some(site)) = dbh.get_site_meta_by_origin(&origin) {
-                                                                                                                    let _ = dbh.insert_icon(site.id, icon_url.as_str(), None, None, mime.as_deref(), vec.clone(), now);
-                                                                                                                }
-                                                                                                            }
-                                                                                                            let _ = proxy_clone.send_event(UserEvent::Favicon(vec));
-                                                                                                            return;
-                                                                                                        }
-                                                                                                    }
-                                                                                                }
-                                                                                            }
-                                                                                        }
-                                                                                    }
-                                                                                }
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-
-                                                            // Fallback: look for <link rel="icon"> or similar
-                                                            let sel = Selector::parse("link[rel]").unwrap();
-                                                            for element in doc.select(&sel) {
-                                                                if let Some(rel) = element.value().attr("rel") {
-                                                                    let rel_l = rel.to_lowercase();
-                                                                    if rel_l.contains("icon") {
-                                                                        if let Some(href) = element.value().attr("href") {
-                                                                            if let Ok(icon_url) = parsed.join(href) {
-                                                                                if let Ok(icon_resp) = reqwest::blocking::get(icon_url.as_str()) {
-                                                                                    if icon_resp.status().is_success() {
-                                                                                        if let Ok(bytes) = icon_resp.bytes() {
-                                                                                            let vec = bytes.to_vec();
-                                                                                            if let Some(dbh) = db::get_global() {
-                                                                                                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).ok();
-                                                                                                let _ = dbh.upsert_site_meta(&origin, None, None, None, now);
-                                                                                                if let Ok(Some(site)) = dbh.get_site_meta_by_origin(&origin) {
-                                                                                                    let _ = dbh.insert_icon(site.id, icon_url.as_str(), None, None, None, vec.clone(), now);
-                                                                                                }
-                                                                                            }
-                                                                                            let _ = proxy_clone.send_event(UserEvent::Favicon(vec));
-                                                                                            return;
-                                                                                        }
-                                                                                    }
-                                                                                }
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-                                                }
-                                            }
-                                        }
-                                    }
+                                let Some(dbh) = db::get_global() else {
+                                    return;
+                                };
+                                let reason = crate::metadata::FetchReason::Opened;
+                                if let Err(e) = crate::metadata::fetch_and_store(&dbh, &url, reason)
+                                {
+                                    eprintln!("metadata fetch for {} failed: {}", url, e);
+                                }
+                                // Use whatever is stored, even when this fetch failed or the
+                                // network policy skipped it.
+                                let icon = crate::urlnorm::origin(&url)
+                                    .and_then(|o| dbh.get_site_meta_by_origin(&o).ok().flatten())
+                                    .and_then(|site| {
+                                        dbh.best_icon(site.id, WINDOW_ICON_PX).ok().flatten()
+                                    });
+                                if let Some(icon) = icon {
+                                    let _ = proxy_clone.send_event(UserEvent::Favicon(icon.data));
                                 }
                             });
                         }
                     }
                     UserEvent::Favicon(bytes) => {
-                        // Try to decode image and set as window icon
-                        match image::load_from_memory(&bytes) {
-                            Ok(img) => {
-                                let rgba = img.to_rgba8();
+                        // Try to decode image (SVG is rasterized) and set as window icon
+                        match crate::svg::decode_icon(&bytes, None, WINDOW_ICON_PX) {
+                            Ok(rgba) => {
                                 let (w, h) = (rgba.width(), rgba.height());
                                 let raw = rgba.into_raw();
                                 if let Ok(icon) = tao::window::Icon::from_rgba(raw, w, h) {
@@ -235,6 +144,10 @@ fn start_manager() -> Sender<UserEvent> {
                             Err(e) => eprintln!("failed to decode favicon: {}", e),
                         }
                     }
+                    UserEvent::Shutdown => {
+                        let _ = window.set_visible(false);
+                        *control_flow = tao::event_loop::ControlFlow::Exit;
+                    }
                 },
                 tao::event::Event::WindowEvent { event, .. } => match event {
                     tao::event::WindowEvent::CloseRequested => {
@@ -251,9 +164,25 @@ fn start_manager() -> Sender<UserEvent> {
     });
 
     let _ = WEBVIEW_TX.set(tx.clone());
+    *WEBVIEW_THREAD.lock().unwrap() = Some(manager);
     tx
 }
 
+/// Close the webview window and stop its thread, waiting for it to finish.
+/// A no-op if no URL was ever opened in the webview.
+pub fn shutdown() -> Result<()> {
+    let Some(manager) = WEBVIEW_THREAD.lock().unwrap().take() else {
+        return Ok(());
+    };
+    if let Some(tx) = WEBVIEW_TX.get() {
+        tx.send(UserEvent::Shutdown)
+            .map_err(|e| anyhow::anyhow!("failed to send webview shutdown: {}", e))?;
+    }
+    manager
+        .join()
+        .map_err(|_| anyhow::anyhow!("webview thread panicked"))
+}
+
 pub fn open_url(url: String) -> Result<()> {
     // Normalize the URL: trim, and if no scheme present, default to http://
     let s = url.trim();
@@ -281,3 +210,18 @@ pub fn open_url(url: String) -> Result<()> {
         .map_err(|e| anyhow::anyhow!("failed to send open_url: {}", e))?;
     Ok(())
 }
+
+/// Open a saved URL and record the visit used for frecency ranking.
+pub fn open_saved_url(id: i64, url: String) -> Result<()> {
+    open_url(url)?;
+    if let Some(dbh) = db::get_global() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        if let Err(e) = dbh.record_visit(id, now) {
+            eprintln!("failed to record visit for url id={}: {}", id, e);
+        }
+    }
+    Ok(())
+}